@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::recorder::{Recorder, RecorderError};
+
+/// Chance of rain (%) at which a forecast counts as predicting rain
+const RAIN_PREDICTED_CHANCE: i64 = 50;
+
+/// Forecast for an hour matched with what was observed during it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub provider: String,
+    /// Whole days between the forecast being issued and the hour it was for
+    pub lead_days: i64,
+    pub forecast_temp_c: f64,
+    pub forecast_chance_of_rain: i64,
+    pub observed_temp_c: f64,
+    pub observed_precip_mm: f64,
+}
+
+/// Error statistics for one provider at one lead time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccuracyStats {
+    pub provider: String,
+    pub lead_days: i64,
+    pub samples: usize,
+    /// Mean absolute error of the temperature (C)
+    pub temp_mae_c: f64,
+    /// Mean of forecast minus observed temperature (C), positive when forecasts run warm
+    pub temp_bias_c: f64,
+    /// Share of hours where rain was forecast (or not) and then fell (or didn't), 0-1
+    pub rain_hit_rate: f64,
+}
+
+/// Accuracy of each provider for one location
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccuracyReport {
+    pub location: String,
+    pub stats: Vec<AccuracyStats>,
+}
+
+impl Verification {
+    fn rain_hit(&self) -> bool {
+        let predicted = self.forecast_chance_of_rain >= RAIN_PREDICTED_CHANCE;
+        let observed = self.observed_precip_mm > 0.0;
+
+        predicted == observed
+    }
+}
+
+/// Recorded forecasts for the location paired with every observation made during the hour they
+/// were for. Forecasts for the hour they were issued in are left out, as they were observations already
+pub fn verifications(
+    recorder: &Recorder,
+    location: &str,
+) -> Result<Vec<Verification>, RecorderError> {
+    let connection = recorder.connect()?;
+    let mut statement = connection.prepare(
+        "SELECT f.provider, (f.time_epoch - f.issued_at) / 86400, f.temp_c, f.chance_of_rain,
+            o.temp_c, o.precip_mm
+         FROM forecasts f
+         JOIN observations o ON o.location = f.location AND o.provider = f.provider
+            AND o.observed_at >= f.time_epoch AND o.observed_at < f.time_epoch + 3600
+         WHERE f.location = ?1 AND f.time_epoch > f.issued_at",
+    )?;
+
+    let verifications = statement
+        .query_map(params![location], |row| {
+            Ok(Verification {
+                provider: row.get(0)?,
+                lead_days: row.get(1)?,
+                forecast_temp_c: row.get(2)?,
+                forecast_chance_of_rain: row.get(3)?,
+                observed_temp_c: row.get(4)?,
+                observed_precip_mm: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<Verification>, rusqlite::Error>>()?;
+
+    Ok(verifications)
+}
+
+/// Locations with recorded observations, in the spelling first recorded
+pub fn recorded_locations(recorder: &Recorder) -> Result<Vec<String>, RecorderError> {
+    let connection = recorder.connect()?;
+    let mut statement = connection
+        .prepare("SELECT location FROM observations GROUP BY location ORDER BY location")?;
+
+    let locations = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+
+    Ok(locations)
+}
+
+/// Error statistics for each provider and lead time, in that order
+pub fn statistics(verifications: &[Verification]) -> Vec<AccuracyStats> {
+    let mut groups: BTreeMap<(&str, i64), Vec<&Verification>> = BTreeMap::new();
+    for verification in verifications {
+        groups
+            .entry((&verification.provider, verification.lead_days))
+            .or_default()
+            .push(verification);
+    }
+
+    groups
+        .into_iter()
+        .map(|((provider, lead_days), group)| {
+            let samples = group.len();
+            let errors: Vec<f64> = group
+                .iter()
+                .map(|v| v.forecast_temp_c - v.observed_temp_c)
+                .collect();
+            let hits = group.iter().filter(|v| v.rain_hit()).count();
+
+            AccuracyStats {
+                provider: provider.to_string(),
+                lead_days,
+                samples,
+                temp_mae_c: errors.iter().map(|error| error.abs()).sum::<f64>() / samples as f64,
+                temp_bias_c: errors.iter().sum::<f64>() / samples as f64,
+                rain_hit_rate: hits as f64 / samples as f64,
+            }
+        })
+        .collect()
+}
+
+/// Accuracy report for the location from everything recorded for it
+pub fn report(recorder: &Recorder, location: &str) -> Result<AccuracyReport, RecorderError> {
+    let verifications = verifications(recorder, location)?;
+
+    Ok(AccuracyReport {
+        location: location.to_string(),
+        stats: statistics(&verifications),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::{
+        recorder::PROVIDER,
+        weather::{fixture_weather, Weather},
+    };
+
+    fn verification(
+        provider: &str,
+        lead_days: i64,
+        error: f64,
+        chance: i64,
+        mm: f64,
+    ) -> Verification {
+        Verification {
+            provider: provider.to_string(),
+            lead_days,
+            forecast_temp_c: 10.0 + error,
+            forecast_chance_of_rain: chance,
+            observed_temp_c: 10.0,
+            observed_precip_mm: mm,
+        }
+    }
+
+    #[test]
+    fn computes_error_per_provider_and_lead_time() {
+        let stats = statistics(&[
+            verification("a", 0, 1.0, 80, 0.4),
+            verification("a", 0, -3.0, 10, 0.2),
+            verification("a", 1, 2.0, 60, 0.0),
+            verification("b", 0, 0.5, 0, 0.0),
+        ]);
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!((stats[0].provider.as_str(), stats[0].lead_days), ("a", 0));
+        assert_eq!(stats[0].samples, 2);
+        assert_eq!(stats[0].temp_mae_c, 2.0);
+        assert_eq!(stats[0].temp_bias_c, -1.0);
+        // rain was forecast and fell, then wasn't forecast and fell anyway
+        assert_eq!(stats[0].rain_hit_rate, 0.5);
+        assert_eq!(stats[1].rain_hit_rate, 0.0);
+        assert_eq!(stats[2].rain_hit_rate, 1.0);
+    }
+
+    #[test]
+    fn verifies_recorded_forecasts_against_later_observations() {
+        let path = env::temp_dir().join(format!("t-weather-accuracy-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let recorder = Recorder::open(path);
+
+        let issued: Weather = fixture_weather();
+        recorder.record("London", &issued).unwrap();
+
+        // two hours later, observed a degree cooler than forecast
+        let hour = issued
+            .hours()
+            .find(|hour| {
+                issued
+                    .current
+                    .as_ref()
+                    .is_some_and(|current| hour.time_epoch == current.last_updated_epoch + 7200)
+            })
+            .unwrap();
+        let mut observed = issued.clone();
+        let current = observed.current.as_mut().unwrap();
+        current.last_updated_epoch = hour.time_epoch + 600;
+        current.last_updated = hour.time + chrono::Duration::minutes(10);
+        current.temp_c = hour.temp_c - 1.0;
+        recorder.record("London", &observed).unwrap();
+
+        let report = report(&recorder, "london").unwrap();
+        assert_eq!(report.stats.len(), 1);
+        assert_eq!(report.stats[0].provider, PROVIDER);
+        assert_eq!(report.stats[0].lead_days, 0);
+        assert_eq!(report.stats[0].samples, 1);
+        assert_eq!(report.stats[0].temp_bias_c, 1.0);
+        assert_eq!(
+            recorded_locations(&recorder).unwrap(),
+            vec![String::from("London")]
+        );
+        let _ = fs::remove_file(recorder.path());
+    }
+}
@@ -0,0 +1,281 @@
+use std::cmp::Reverse;
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::weather::{Hour, Weather};
+
+/// Points taken off an hour's score for each unit it falls outside the profile
+const TEMP_PENALTY_PER_DEGREE: f64 = 5.0;
+const RAIN_PENALTY_PER_PERCENT: f64 = 1.5;
+const WIND_PENALTY_PER_KPH: f64 = 3.0;
+const UV_PENALTY_PER_INDEX: f64 = 10.0;
+
+/// Conditions an outdoor activity is comfortable in
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityProfile {
+    pub name: String,
+    pub min_temp_c: f64,
+    pub max_temp_c: f64,
+    pub max_chance_of_rain: i64,
+    pub max_wind_kph: f64,
+    pub max_uv: f64,
+    /// Length of the window the activity needs, in hours
+    pub hours: u32,
+}
+
+/// Profile as written in the config file. Settings left out are taken from the built-in
+/// profile with the same name, or from the running profile
+#[derive(Debug, Deserialize)]
+struct ProfileEntry {
+    name: String,
+    min_temp_c: Option<f64>,
+    max_temp_c: Option<f64>,
+    max_chance_of_rain: Option<i64>,
+    max_wind_kph: Option<f64>,
+    max_uv: Option<f64>,
+    hours: Option<u32>,
+}
+
+/// Block of consecutive hours scored against a profile, 100 meets every condition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActivityWindow {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub score: u8,
+}
+
+/// Best windows for one activity, as printed with --json
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivityRanking {
+    pub activity: String,
+    pub windows: Vec<ActivityWindow>,
+}
+
+impl Default for ActivityProfile {
+    fn default() -> Self {
+        ActivityProfile {
+            name: String::from("Running"),
+            min_temp_c: 5.0,
+            max_temp_c: 20.0,
+            max_chance_of_rain: 20,
+            max_wind_kph: 25.0,
+            max_uv: 6.0,
+            hours: 1,
+        }
+    }
+}
+
+impl ActivityProfile {
+    pub fn defaults() -> Vec<ActivityProfile> {
+        vec![
+            ActivityProfile::default(),
+            ActivityProfile {
+                name: String::from("Cycling"),
+                min_temp_c: 10.0,
+                max_temp_c: 25.0,
+                max_chance_of_rain: 20,
+                max_wind_kph: 20.0,
+                max_uv: 7.0,
+                hours: 2,
+            },
+        ]
+    }
+
+    /// Built-in profiles with the configured ones on top. A configured profile changes the
+    /// built-in one with the same name, others are added after them
+    pub fn deserialize_with_defaults<'de, D>(
+        deserializer: D,
+    ) -> Result<Vec<ActivityProfile>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut profiles = ActivityProfile::defaults();
+        for entry in Vec::<ProfileEntry>::deserialize(deserializer)? {
+            let profile = match profiles
+                .iter_mut()
+                .find(|profile| profile.name.eq_ignore_ascii_case(&entry.name))
+            {
+                Some(profile) => profile,
+                None => {
+                    profiles.push(ActivityProfile {
+                        name: entry.name.clone(),
+                        ..ActivityProfile::default()
+                    });
+                    profiles.last_mut().expect("a profile was just added")
+                }
+            };
+            entry.apply(profile);
+        }
+
+        Ok(profiles)
+    }
+
+    /// Score (0-100) of a single hour, points are taken off for every condition outside the profile
+    pub fn score_hour(&self, hour: &Hour) -> f64 {
+        let temp_outside = if hour.temp_c < self.min_temp_c {
+            self.min_temp_c - hour.temp_c
+        } else {
+            (hour.temp_c - self.max_temp_c).max(0.0)
+        };
+        let rain_over = (hour.chance_of_rain - self.max_chance_of_rain).max(0) as f64;
+        let wind_over = (hour.wind_kph - self.max_wind_kph).max(0.0);
+        let uv_over = (hour.uv - self.max_uv).max(0.0);
+
+        let penalty = temp_outside * TEMP_PENALTY_PER_DEGREE
+            + rain_over * RAIN_PENALTY_PER_PERCENT
+            + wind_over * WIND_PENALTY_PER_KPH
+            + uv_over * UV_PENALTY_PER_INDEX;
+
+        (100.0 - penalty).max(0.0)
+    }
+}
+
+impl ProfileEntry {
+    fn apply(self, profile: &mut ActivityProfile) {
+        profile.min_temp_c = self.min_temp_c.unwrap_or(profile.min_temp_c);
+        profile.max_temp_c = self.max_temp_c.unwrap_or(profile.max_temp_c);
+        profile.max_chance_of_rain = self
+            .max_chance_of_rain
+            .unwrap_or(profile.max_chance_of_rain);
+        profile.max_wind_kph = self.max_wind_kph.unwrap_or(profile.max_wind_kph);
+        profile.max_uv = self.max_uv.unwrap_or(profile.max_uv);
+        profile.hours = self.hours.unwrap_or(profile.hours);
+    }
+}
+
+/// Ranks the non-overlapping windows from the location's current hour onwards, best first.
+/// A window scores as well as its worst hour, ties go to the earlier window
+pub fn best_windows(
+    weather: &Weather,
+    profile: &ActivityProfile,
+    limit: usize,
+) -> Vec<ActivityWindow> {
+    let length = profile.hours.max(1) as usize;
+    let now = weather.location.localtime;
+    let hours: Vec<&Hour> = weather
+        .hours()
+        .filter(|hour| hour.time + Duration::hours(1) > now)
+        .collect();
+
+    let mut candidates: Vec<ActivityWindow> = hours
+        .windows(length)
+        // skip windows with gaps between the fetched hours
+        .filter(|window| {
+            window[length - 1].time - window[0].time == Duration::hours(length as i64 - 1)
+        })
+        .map(|window| ActivityWindow {
+            start: window[0].time,
+            end: window[length - 1].time + Duration::hours(1),
+            score: window
+                .iter()
+                .map(|hour| profile.score_hour(hour))
+                .fold(100.0, f64::min)
+                .round() as u8,
+        })
+        .collect();
+    // stable sort, so equal scores stay in time order
+    candidates.sort_by_key(|window| Reverse(window.score));
+
+    let mut best: Vec<ActivityWindow> = vec![];
+    for candidate in candidates {
+        if best.len() == limit {
+            break;
+        }
+
+        let overlaps = best
+            .iter()
+            .any(|window| candidate.start < window.end && window.start < candidate.end);
+        if !overlaps {
+            best.push(candidate);
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn ranks_non_overlapping_windows() {
+        let weather = fixture_weather();
+        let profile = ActivityProfile {
+            hours: 3,
+            ..ActivityProfile::default()
+        };
+
+        let windows = best_windows(&weather, &profile, 5);
+        assert_eq!(windows.len(), 5);
+        assert!(windows
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+        for (i, a) in windows.iter().enumerate() {
+            assert_eq!(a.end - a.start, Duration::hours(3));
+            // the first window can't start before the current hour
+            assert!(a.end > weather.location.localtime);
+            for b in &windows[i + 1..] {
+                assert!(a.end <= b.start || b.end <= a.start);
+            }
+        }
+    }
+
+    #[test]
+    fn penalises_conditions_outside_profile() {
+        let weather = fixture_weather();
+        let hour = weather.hours().next().unwrap();
+
+        let comfortable = ActivityProfile {
+            min_temp_c: -50.0,
+            max_temp_c: 50.0,
+            max_chance_of_rain: 100,
+            max_wind_kph: 200.0,
+            max_uv: 20.0,
+            ..ActivityProfile::default()
+        };
+        assert_eq!(comfortable.score_hour(hour), 100.0);
+
+        let too_warm = ActivityProfile {
+            max_temp_c: hour.temp_c - 2.0,
+            ..comfortable
+        };
+        assert_eq!(too_warm.score_hour(hour), 90.0);
+    }
+
+    #[test]
+    fn adds_configured_profiles_to_the_built_in_ones() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "ActivityProfile::deserialize_with_defaults")]
+            activities: Vec<ActivityProfile>,
+        }
+        let parse = |toml: &str| toml::from_str::<Config>(toml).map(|config| config.activities);
+
+        let profiles = parse(
+            r#"
+            [[activities]]
+            name = "Hiking"
+            hours = 4
+
+            [[activities]]
+            name = "cycling"
+            max_wind_kph = 30
+            "#,
+        )
+        .unwrap();
+        let names: Vec<&str> = profiles
+            .iter()
+            .map(|profile| profile.name.as_str())
+            .collect();
+        assert_eq!(names, ["Running", "Cycling", "Hiking"]);
+        assert_eq!(profiles[0], ActivityProfile::default());
+        // cycling keeps its other settings
+        assert_eq!(profiles[1].max_wind_kph, 30.0);
+        assert_eq!(profiles[1].hours, 2);
+        assert_eq!(profiles[2].hours, 4);
+
+        assert!(parse("[[activities]]\nhours = 4").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::{
+    day_parts::DayPart,
+    weather::{Hour, Weather},
+};
+
+/// Time windows advice is given for and the thresholds that trigger each recommendation
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdviceConfig {
+    pub windows: Vec<DayPart>,
+    /// Chance of rain (%) from which an umbrella is recommended
+    pub umbrella_chance: i64,
+    /// Feels-like temperature (C) below which a jacket is recommended
+    pub jacket_feelslike_c: f64,
+    /// UV index from which sunscreen is recommended
+    pub sunscreen_uv: f64,
+    /// Wind chill (C) at or below which gloves are recommended
+    pub gloves_windchill_c: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recommendation {
+    Umbrella,
+    Jacket,
+    Sunscreen,
+    Gloves,
+}
+
+/// Recommendations for the next occurrence of a time window
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advice<'a> {
+    pub window: &'a DayPart,
+    pub date: NaiveDate,
+    /// Each recommendation with the forecast value that triggered it, e.g. "70% chance of rain"
+    pub recommendations: Vec<(Recommendation, String)>,
+}
+
+impl Default for AdviceConfig {
+    fn default() -> Self {
+        AdviceConfig {
+            windows: vec![
+                DayPart::new("Morning commute", 8, 9),
+                DayPart::new("Evening commute", 17, 18),
+            ],
+            umbrella_chance: 50,
+            jacket_feelslike_c: 12.0,
+            sunscreen_uv: 3.0,
+            gloves_windchill_c: 5.0,
+        }
+    }
+}
+
+impl Recommendation {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Recommendation::Umbrella => "☂",
+            Recommendation::Jacket => "🧥",
+            Recommendation::Sunscreen => "🧴",
+            Recommendation::Gloves => "🧤",
+        }
+    }
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Recommendation::Umbrella => "Umbrella",
+            Recommendation::Jacket => "Jacket",
+            Recommendation::Sunscreen => "Sunscreen",
+            Recommendation::Gloves => "Gloves",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl AdviceConfig {
+    /// Recommendations for the hours of a window, empty when nothing is needed
+    pub fn recommend(&self, hours: &[&Hour]) -> Vec<(Recommendation, String)> {
+        let mut recommendations = vec![];
+
+        let max_rain = hours.iter().map(|hour| hour.chance_of_rain).max();
+        if let Some(chance) = max_rain.filter(|chance| *chance >= self.umbrella_chance) {
+            recommendations.push((
+                Recommendation::Umbrella,
+                format!("{}% chance of rain", chance),
+            ));
+        }
+
+        let min_feelslike = min(hours.iter().map(|hour| hour.feelslike_c));
+        if let Some(feelslike) = min_feelslike.filter(|temp| *temp < self.jacket_feelslike_c) {
+            recommendations.push((Recommendation::Jacket, format!("feels like {}C", feelslike)));
+        }
+
+        let max_uv = max(hours.iter().map(|hour| hour.uv));
+        if let Some(uv) = max_uv.filter(|uv| *uv >= self.sunscreen_uv) {
+            recommendations.push((Recommendation::Sunscreen, format!("UV index {}", uv)));
+        }
+
+        let min_windchill = min(hours.iter().map(|hour| hour.windchill_c));
+        if let Some(windchill) = min_windchill.filter(|temp| *temp <= self.gloves_windchill_c) {
+            recommendations.push((Recommendation::Gloves, format!("wind chill {}C", windchill)));
+        }
+
+        recommendations
+    }
+}
+
+fn min(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.reduce(f64::min)
+}
+
+fn max(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.reduce(f64::max)
+}
+
+/// Advice for the next occurrence of each window, today's if it hasn't ended yet, otherwise
+/// tomorrow's. Windows outside the fetched forecast are left out
+pub fn advise<'a>(weather: &Weather, config: &'a AdviceConfig) -> Vec<Advice<'a>> {
+    let now = weather.location.localtime;
+
+    config
+        .windows
+        .iter()
+        .filter_map(|window| {
+            let (start, end) = window.next_range(now);
+            let hours: Vec<&Hour> = weather.hours_between(start.max(now), end).collect();
+            if hours.is_empty() {
+                return None;
+            }
+
+            Some(Advice {
+                window,
+                date: start.date(),
+                recommendations: config.recommend(&hours),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn advises_upcoming_windows() {
+        let weather = fixture_weather();
+        let config = AdviceConfig::default();
+
+        // local time is 09:08, so the morning commute has passed and tomorrow's is used
+        let advice = advise(&weather, &config);
+        assert_eq!(advice.len(), 2);
+        assert_eq!(advice[0].window.name, "Morning commute");
+        assert_eq!(advice[0].date, NaiveDate::from_ymd_opt(2024, 5, 2).unwrap());
+        assert_eq!(advice[1].window.name, "Evening commute");
+        assert_eq!(advice[1].date, NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+    }
+
+    #[test]
+    fn thresholds_are_configurable() {
+        let weather = fixture_weather();
+        let hours: Vec<&Hour> = weather.hours().collect();
+
+        let mut config = AdviceConfig {
+            umbrella_chance: 101,
+            jacket_feelslike_c: -100.0,
+            sunscreen_uv: 100.0,
+            gloves_windchill_c: -100.0,
+            ..AdviceConfig::default()
+        };
+        assert!(config.recommend(&hours).is_empty());
+
+        config.umbrella_chance = 0;
+        let recommendations = config.recommend(&hours);
+        assert_eq!(recommendations.len(), 1);
+        assert_eq!(recommendations[0].0, Recommendation::Umbrella);
+    }
+}
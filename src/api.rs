@@ -1,29 +1,337 @@
-use crate::weather::Weather;
+use std::fmt;
 
-const URL: &str = "http://api.weatherapi.com/v1/forecast.json";
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-pub fn get_current_weather(location: &str, days: Option<u8>) -> Option<Weather> {
-    match api_fetch(location, days) {
-        Ok(result) => match Some(result) {
-            Some(result) => result,
-            None => None,
-        },
-        Err(_) => None,
+use crate::{open_meteo, weather::Weather};
+
+const URL: &str = "http://api.weatherapi.com/v1";
+/// Environment variable replacing URL, e.g. with the address of `t-weather mock-api`
+const URL_VARIABLE: &str = "API_URL";
+
+/// Error code weatherapi.com returns when the API key's plan doesn't include an endpoint
+const NOT_IN_PLAN: i64 = 2009;
+
+/// Kind of weather data requested for a location
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Current conditions, alerts and air quality along with a forecast for this many days
+    Forecast(u8),
+    /// Recorded weather for each day from the first date to the last (inclusive)
+    History(NaiveDate, NaiveDate, Provider),
+    /// Long-range forecast for a single day 14-300 days ahead, with lower confidence
+    Future(NaiveDate),
+    /// Wave, swell, water temperature and tide forecast for this many days
+    Marine(u8, Provider),
+}
+
+/// Where weather data is fetched from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Provider {
+    /// weatherapi.com, using the key in KEY
+    #[default]
+    #[value(name = "weatherapi")]
+    WeatherApi,
+    /// Open-Meteo, which needs no key
+    OpenMeteo,
+}
+
+/// Location matching a search, as returned by search.json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub id: i64,
+    pub name: String,
+    pub region: String,
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub url: String,
+}
+
+/// Where requests are sent and the key they are made with
+#[derive(Debug, Clone, PartialEq)]
+struct Client {
+    url: String,
+    key: String,
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// No API key was found in the KEY environment variable
+    MissingKey,
+    /// API_URL is not a valid URL
+    InvalidUrl(String),
+    /// Request could not be sent, or the response could not be read
+    Request(reqwest::Error),
+    /// weatherapi.com rejected the request, e.g. unknown location or invalid key
+    Provider { code: i64, message: String },
+    /// Open-Meteo rejected the request or found no such location
+    OpenMeteo(String),
+    /// Response was not weather data in the expected format
+    Parse(serde_json::Error),
+}
+
+/// Body of an unsuccessful response, e.g. {"error":{"code":1006,"message":"No matching location found."}}
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ProviderError,
+}
+
+#[derive(Deserialize)]
+struct ProviderError {
+    code: i64,
+    message: String,
+}
+
+impl Query {
+    fn endpoint(&self) -> &str {
+        match self {
+            Query::Forecast(_) => "forecast.json",
+            Query::History(.., Provider::WeatherApi) => "history.json",
+            Query::History(.., Provider::OpenMeteo) => "archive",
+            Query::Future(_) => "future.json",
+            Query::Marine(_, Provider::WeatherApi) => "marine.json",
+            Query::Marine(_, Provider::OpenMeteo) => "marine",
+        }
+    }
+
+    /// Identifies the request for a location, e.g. forecast.json-days=2-alerts=yes-aqi=yes
+    pub fn cache_key(&self) -> String {
+        let params: Vec<String> = self
+            .params()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        format!("{}-{}", self.endpoint(), params.join("-"))
+    }
+
+    fn params(&self) -> Vec<(&str, String)> {
+        match self {
+            Query::Forecast(days) => vec![
+                ("days", days.to_string()),
+                ("alerts", String::from("yes")),
+                ("aqi", String::from("yes")),
+            ],
+            Query::History(from, to, _) => vec![
+                ("dt", from.format("%Y-%m-%d").to_string()),
+                ("end_dt", to.format("%Y-%m-%d").to_string()),
+            ],
+            Query::Future(date) => vec![("dt", date.format("%Y-%m-%d").to_string())],
+            Query::Marine(days, _) => {
+                vec![("days", days.to_string()), ("tides", String::from("yes"))]
+            }
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::MissingKey => write!(f, "No API key found, add KEY to your .env file"),
+            ApiError::InvalidUrl(url) => write!(f, "Invalid weather API URL {}", url),
+            ApiError::Request(e) => write!(f, "Unable to reach weather API: {}", e),
+            ApiError::Provider {
+                code: NOT_IN_PLAN, ..
+            } => write!(
+                f,
+                "Your weatherapi.com plan doesn't include this data (long-range forecasts need a paid plan)"
+            ),
+            ApiError::Provider { code, message } => write!(f, "{} (error {})", message, code),
+            ApiError::OpenMeteo(reason) => write!(f, "{} (Open-Meteo)", reason),
+            ApiError::Parse(e) => write!(f, "Unexpected response from weather API: {}", e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError::Request(e)
     }
 }
 
-fn api_fetch(location: &str, days: Option<u8>) -> Result<Option<Weather>, reqwest::Error> {
-    let api_key: &str = &std::env::var("KEY").unwrap();
-    let params = [
-        ("key", api_key),
-        ("q", location),
-        ("days", &days.unwrap_or(1).to_string()),
-    ];
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Parse(e)
+    }
+}
 
-    let url = reqwest::Url::parse_with_params(URL, &params).unwrap();
-    let response = reqwest::blocking::get(url)?;
-    let body = response.text().unwrap();
-    let response: Option<Weather> = serde_json::from_str(&body).unwrap_or_default();
+impl Client {
+    /// weatherapi.com, or API_URL when set, with the key in the KEY environment variable
+    fn from_env() -> Result<Client, ApiError> {
+        let key = std::env::var("KEY").map_err(|_| ApiError::MissingKey)?;
+        let url = std::env::var(URL_VARIABLE).unwrap_or_else(|_| String::from(URL));
+
+        Ok(Client { url, key })
+    }
 
-    Ok(response)
+    fn get_weather(&self, location: &str, query: &Query) -> Result<Weather, ApiError> {
+        let mut weather: Weather = self.api_fetch(query.endpoint(), location, query.params())?;
+        weather.fill_astro();
+
+        Ok(weather)
+    }
+
+    fn search(&self, text: &str) -> Result<Vec<SearchResult>, ApiError> {
+        self.api_fetch("search.json", text, vec![])
+    }
+
+    fn api_fetch<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        location: &str,
+        query_params: Vec<(&str, String)>,
+    ) -> Result<T, ApiError> {
+        let mut params = vec![("key", self.key.clone()), ("q", location.to_string())];
+        params.extend(query_params);
+
+        let url = format!("{}/{}", self.url.trim_end_matches('/'), endpoint);
+        let url = reqwest::Url::parse_with_params(&url, &params)
+            .map_err(|_| ApiError::InvalidUrl(self.url.clone()))?;
+        let body = reqwest::blocking::get(url)?.text()?;
+
+        // errors are reported in the body alongside a 4xx status
+        if let Ok(response) = serde_json::from_str::<ErrorResponse>(&body) {
+            return Err(ApiError::Provider {
+                code: response.error.code,
+                message: response.error.message,
+            });
+        }
+
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+pub fn get_weather(location: &str, query: &Query) -> Result<Weather, ApiError> {
+    match query {
+        Query::History(from, to, Provider::OpenMeteo) => {
+            open_meteo::Client::from_env().history(location, *from, *to)
+        }
+        Query::Marine(days, Provider::OpenMeteo) => {
+            open_meteo::Client::from_env().marine(location, *days)
+        }
+        _ => Client::from_env()?.get_weather(location, query),
+    }
+}
+
+/// Locations whose name starts with or contains the search text
+pub fn search(text: &str) -> Result<Vec<SearchResult>, ApiError> {
+    Client::from_env()?.search(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, EXHAUSTED_KEY, INVALID_KEY, UNKNOWN_LOCATION};
+
+    fn client(key: &str) -> Client {
+        Client {
+            url: mock::spawn(),
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn fetches_each_kind_of_query() {
+        let client = client("test");
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+
+        for query in [
+            Query::Forecast(2),
+            Query::History(date, date, Provider::WeatherApi),
+            Query::Future(date),
+            Query::Marine(1, Provider::WeatherApi),
+        ] {
+            let weather = client.get_weather("London", &query).unwrap();
+            assert_eq!(weather.location.name, "London", "{:?}", query);
+            assert!(weather.forecast.forecastday[0].astro.sunrise.is_some());
+        }
+
+        let dates = |weather: &Weather| -> Vec<String> {
+            weather
+                .forecast
+                .forecastday
+                .iter()
+                .map(|day| day.date.to_string())
+                .collect()
+        };
+
+        let weather = client.get_weather("London", &Query::Forecast(3)).unwrap();
+        assert!(weather.current.is_some());
+        assert_eq!(dates(&weather), ["2024-05-01", "2024-05-02", "2024-05-03"]);
+        let hour = &weather.forecast.forecastday[2].hour[0];
+        assert_eq!(hour.time.to_string(), "2024-05-03 00:00:00");
+
+        let to = NaiveDate::from_ymd_opt(2024, 4, 22).unwrap();
+        let from = NaiveDate::from_ymd_opt(2024, 4, 20).unwrap();
+        let weather = client
+            .get_weather("London", &Query::History(from, to, Provider::WeatherApi))
+            .unwrap();
+        assert!(weather.current.is_none());
+        assert!(weather.alerts.is_empty());
+        assert_eq!(dates(&weather), ["2024-04-20", "2024-04-21", "2024-04-22"]);
+        assert_eq!(
+            weather.forecast.forecastday[0].day.condition.text,
+            "Moderate rain"
+        );
+
+        let date = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+        let weather = client.get_weather("London", &Query::Future(date)).unwrap();
+        assert!(weather.current.is_none());
+        assert_eq!(dates(&weather), ["2024-09-01"]);
+        assert_eq!(weather.forecast.forecastday[0].hour.len(), 8);
+
+        let weather = client
+            .get_weather("London", &Query::Marine(2, Provider::WeatherApi))
+            .unwrap();
+        assert_eq!(dates(&weather), ["2024-05-01", "2024-05-02"]);
+        let day = &weather.forecast.forecastday[1];
+        let tides: Vec<String> = day
+            .tides()
+            .map(|tide| format!("{} {}", tide.tide_type, tide.tide_time))
+            .collect();
+        assert_eq!(tides[0], "HIGH 2024-05-02 03:02:00");
+        assert_eq!(tides.len(), 4);
+        assert_eq!(day.hour[12].sig_ht_mt, Some(0.6));
+        assert_eq!(day.hour[12].swell_period_secs, Some(8.7));
+        assert_eq!(day.hour[12].water_temp_c, Some(11.4));
+
+        let results = client.search("lon").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].country, "Canada");
+        assert!(client.search("Paris").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_provider_errors() {
+        let code = |result: Result<Weather, ApiError>| match result {
+            Err(ApiError::Provider { code, .. }) => Some(code),
+            _ => None,
+        };
+        let query = Query::Forecast(1);
+
+        assert_eq!(
+            code(client("test").get_weather(UNKNOWN_LOCATION, &query)),
+            Some(1006)
+        );
+        assert_eq!(
+            code(client(INVALID_KEY).get_weather("London", &query)),
+            Some(2006)
+        );
+        assert_eq!(
+            code(client(EXHAUSTED_KEY).get_weather("London", &query)),
+            Some(2007)
+        );
+        assert_eq!(code(client("").get_weather("London", &query)), Some(1002));
+
+        let invalid = Client {
+            url: String::from("not a url"),
+            key: String::from("test"),
+        };
+        assert!(matches!(
+            invalid.get_weather("London", &query),
+            Err(ApiError::InvalidUrl(_))
+        ));
+    }
 }
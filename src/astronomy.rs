@@ -0,0 +1,388 @@
+use std::f64::consts::PI;
+
+use chrono::{Datelike, NaiveDate, NaiveTime};
+
+/// Altitude of the sun's centre at sunrise/sunset, allowing for refraction and the solar disc
+const SUNRISE_ALTITUDE: f64 = -0.833;
+
+const GOLDEN_HOUR_UPPER: f64 = 6.0;
+const GOLDEN_HOUR_LOWER: f64 = -4.0;
+const BLUE_HOUR_LOWER: f64 = -6.0;
+
+const MINUTES_PER_DAY: f64 = 1440.0;
+
+/// Mean length of a lunar cycle, in days
+const SYNODIC_MONTH: f64 = 29.530588853;
+/// Julian day of a reference new moon (2000-01-06 18:14 UTC)
+const REFERENCE_NEW_MOON: f64 = 2451550.26;
+
+/// Depth of the sun below the horizon that bounds each stage of twilight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Twilight {
+    Civil,
+    Nautical,
+    Astronomical,
+}
+
+impl Twilight {
+    fn altitude(&self) -> f64 {
+        match self {
+            Twilight::Civil => -6.0,
+            Twilight::Nautical => -12.0,
+            Twilight::Astronomical => -18.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoonPhase {
+    NewMoon,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    FullMoon,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+impl MoonPhase {
+    /// Phase for the given age of the moon (days since new moon)
+    fn from_age(age: f64) -> MoonPhase {
+        // each principal phase occupies roughly a day either side of its exact moment
+        match age / SYNODIC_MONTH * 8.0 {
+            x if x < 0.5 => MoonPhase::NewMoon,
+            x if x < 1.5 => MoonPhase::WaxingCrescent,
+            x if x < 2.5 => MoonPhase::FirstQuarter,
+            x if x < 3.5 => MoonPhase::WaxingGibbous,
+            x if x < 4.5 => MoonPhase::FullMoon,
+            x if x < 5.5 => MoonPhase::WaningGibbous,
+            x if x < 6.5 => MoonPhase::LastQuarter,
+            x if x < 7.5 => MoonPhase::WaningCrescent,
+            _ => MoonPhase::NewMoon,
+        }
+    }
+
+    /// Parses the phase names used by weatherapi.com, e.g. "Waxing Gibbous"
+    pub fn from_name(name: &str) -> Option<MoonPhase> {
+        [
+            MoonPhase::NewMoon,
+            MoonPhase::WaxingCrescent,
+            MoonPhase::FirstQuarter,
+            MoonPhase::WaxingGibbous,
+            MoonPhase::FullMoon,
+            MoonPhase::WaningGibbous,
+            MoonPhase::LastQuarter,
+            MoonPhase::WaningCrescent,
+        ]
+        .into_iter()
+        .find(|phase| phase.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MoonPhase::NewMoon => "New Moon",
+            MoonPhase::WaxingCrescent => "Waxing Crescent",
+            MoonPhase::FirstQuarter => "First Quarter",
+            MoonPhase::WaxingGibbous => "Waxing Gibbous",
+            MoonPhase::FullMoon => "Full Moon",
+            MoonPhase::WaningGibbous => "Waning Gibbous",
+            MoonPhase::LastQuarter => "Last Quarter",
+            MoonPhase::WaningCrescent => "Waning Crescent",
+        }
+    }
+
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            MoonPhase::NewMoon => "🌑",
+            MoonPhase::WaxingCrescent => "🌒",
+            MoonPhase::FirstQuarter => "🌓",
+            MoonPhase::WaxingGibbous => "🌔",
+            MoonPhase::FullMoon => "🌕",
+            MoonPhase::WaningGibbous => "🌖",
+            MoonPhase::LastQuarter => "🌗",
+            MoonPhase::WaningCrescent => "🌘",
+        }
+    }
+}
+
+/// State of the moon at local noon on a given date
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Moon {
+    /// Days since the last new moon
+    pub age: f64,
+    pub phase: MoonPhase,
+    /// Percentage of the visible disc that is lit
+    pub illumination: f64,
+}
+
+impl Moon {
+    /// `utc_offset` is the offset of local time from UTC in seconds
+    pub fn new(date: NaiveDate, utc_offset: i32) -> Moon {
+        let age = (local_noon_julian_day(date, utc_offset as f64 / 60.0) - REFERENCE_NEW_MOON)
+            .rem_euclid(SYNODIC_MONTH);
+        let illumination = (1.0 - (2.0 * PI * age / SYNODIC_MONTH).cos()) / 2.0 * 100.0;
+
+        Moon {
+            age,
+            phase: MoonPhase::from_age(age),
+            illumination,
+        }
+    }
+}
+
+/// Span of local time between two sun positions, e.g. the evening golden hour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Period {
+    pub fn format(&self, fmt: &str) -> String {
+        format!("{}-{}", self.start.format(fmt), self.end.format(fmt))
+    }
+}
+
+/// Position of the sun over a single day at a fixed location
+/// Based on the NOAA solar calculator, accurate to roughly a minute for latitudes within +/-72 degrees
+pub struct SolarDay {
+    lat: f64,
+    lon: f64,
+    utc_offset_minutes: f64,
+    declination: f64,
+    equation_of_time: f64,
+}
+
+impl SolarDay {
+    /// `utc_offset` is the offset of local time from UTC in seconds
+    pub fn new(lat: f64, lon: f64, date: NaiveDate, utc_offset: i32) -> SolarDay {
+        let utc_offset_minutes = utc_offset as f64 / 60.0;
+
+        // julian century at local noon
+        let jc = (local_noon_julian_day(date, utc_offset_minutes) - 2451545.0) / 36525.0;
+
+        let mean_long = (280.46646 + jc * (36000.76983 + jc * 0.0003032)).rem_euclid(360.0);
+        let mean_anomaly = 357.52911 + jc * (35999.05029 - 0.0001537 * jc);
+        let eccentricity = 0.016708634 - jc * (0.000042037 + 0.0000001267 * jc);
+
+        let m = mean_anomaly.to_radians();
+        let centre = m.sin() * (1.914602 - jc * (0.004817 + 0.000014 * jc))
+            + (2.0 * m).sin() * (0.019993 - 0.000101 * jc)
+            + (3.0 * m).sin() * 0.000289;
+
+        let omega = (125.04 - 1934.136 * jc).to_radians();
+        let apparent_long = (mean_long + centre - 0.00569 - 0.00478 * omega.sin()).to_radians();
+
+        let mean_obliquity =
+            23.0 + (26.0 + (21.448 - jc * (46.815 + jc * (0.00059 - jc * 0.001813))) / 60.0) / 60.0;
+        let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+
+        let declination = (obliquity.sin() * apparent_long.sin()).asin();
+
+        let y = (obliquity / 2.0).tan().powi(2);
+        let l0 = mean_long.to_radians();
+        let equation_of_time = 4.0
+            * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+                + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+                - 0.5 * y * y * (4.0 * l0).sin()
+                - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+            .to_degrees();
+
+        SolarDay {
+            lat,
+            lon,
+            utc_offset_minutes,
+            declination,
+            equation_of_time,
+        }
+    }
+
+    pub fn solar_noon(&self) -> NaiveTime {
+        minutes_to_time(self.solar_noon_minutes())
+    }
+
+    /// Returns None during polar day/night
+    pub fn sunrise(&self) -> Option<NaiveTime> {
+        self.rising(SUNRISE_ALTITUDE)
+    }
+
+    /// Returns None during polar day/night
+    pub fn sunset(&self) -> Option<NaiveTime> {
+        self.setting(SUNRISE_ALTITUDE)
+    }
+
+    /// Start of morning twilight, when the sun rises past the twilight's lower bound
+    pub fn dawn(&self, twilight: Twilight) -> Option<NaiveTime> {
+        self.rising(twilight.altitude())
+    }
+
+    /// End of evening twilight, when the sun sets past the twilight's lower bound
+    pub fn dusk(&self, twilight: Twilight) -> Option<NaiveTime> {
+        self.setting(twilight.altitude())
+    }
+
+    /// Local time at which the rising sun reaches `altitude` (in degrees)
+    /// Returns None if the sun never reaches that altitude on this day
+    pub fn rising(&self, altitude: f64) -> Option<NaiveTime> {
+        let hour_angle = self.hour_angle(altitude)?;
+        Some(minutes_to_time(
+            self.solar_noon_minutes() - 4.0 * hour_angle,
+        ))
+    }
+
+    /// Local time at which the setting sun drops to `altitude` (in degrees)
+    /// Returns None if the sun never reaches that altitude on this day
+    pub fn setting(&self, altitude: f64) -> Option<NaiveTime> {
+        let hour_angle = self.hour_angle(altitude)?;
+        Some(minutes_to_time(
+            self.solar_noon_minutes() + 4.0 * hour_angle,
+        ))
+    }
+
+    /// Morning and evening golden hour, while the sun is between -4 and 6 degrees
+    pub fn golden_hour(&self) -> Option<(Period, Period)> {
+        self.twin_periods(GOLDEN_HOUR_LOWER, GOLDEN_HOUR_UPPER)
+    }
+
+    /// Morning and evening blue hour, while the sun is between -6 and -4 degrees
+    pub fn blue_hour(&self) -> Option<(Period, Period)> {
+        self.twin_periods(BLUE_HOUR_LOWER, GOLDEN_HOUR_LOWER)
+    }
+
+    fn twin_periods(&self, lower: f64, upper: f64) -> Option<(Period, Period)> {
+        let morning = Period {
+            start: self.rising(lower)?,
+            end: self.rising(upper)?,
+        };
+        let evening = Period {
+            start: self.setting(upper)?,
+            end: self.setting(lower)?,
+        };
+
+        Some((morning, evening))
+    }
+
+    fn solar_noon_minutes(&self) -> f64 {
+        720.0 - 4.0 * self.lon - self.equation_of_time + self.utc_offset_minutes
+    }
+
+    /// Hour angle (in degrees) between solar noon and the sun crossing `altitude`
+    fn hour_angle(&self, altitude: f64) -> Option<f64> {
+        let lat = self.lat.to_radians();
+        let cos_hour_angle = (altitude.to_radians().sin() - lat.sin() * self.declination.sin())
+            / (lat.cos() * self.declination.cos());
+
+        if (-1.0..=1.0).contains(&cos_hour_angle) {
+            Some(cos_hour_angle.acos().to_degrees())
+        } else {
+            None
+        }
+    }
+}
+
+fn local_noon_julian_day(date: NaiveDate, utc_offset_minutes: f64) -> f64 {
+    date.num_days_from_ce() as f64 + 1721425.0 - utc_offset_minutes / MINUTES_PER_DAY
+}
+
+/// Converts minutes past local midnight to a time of day, wrapping around midnight
+fn minutes_to_time(minutes: f64) -> NaiveTime {
+    let seconds = (minutes.rem_euclid(MINUTES_PER_DAY) * 60.0).round() as u32;
+    NaiveTime::from_num_seconds_from_midnight_opt(seconds % 86400, 0).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+    const SYDNEY: (f64, f64) = (-33.8688, 151.2093);
+    const TROMSO: (f64, f64) = (69.6492, 18.9553);
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    /// Reference tables round to the minute, so allow a couple of minutes either way
+    fn assert_close(actual: Option<NaiveTime>, expected: NaiveTime) {
+        let actual = actual.expect("expected a time");
+        let diff = (actual - expected).num_seconds().abs();
+        assert!(
+            diff <= 120,
+            "{actual} is not within 2 minutes of {expected}"
+        );
+    }
+
+    #[test]
+    fn london_summer_solstice() {
+        let day = SolarDay::new(LONDON.0, LONDON.1, date(2024, 6, 21), 3600);
+
+        assert_close(day.sunrise(), time(4, 43));
+        assert_close(day.sunset(), time(21, 21));
+        assert_close(Some(day.solar_noon()), time(13, 2));
+        // the sun never drops 18 degrees below the horizon in a London June
+        assert_eq!(day.dawn(Twilight::Astronomical), None);
+    }
+
+    #[test]
+    fn sydney_summer_solstice() {
+        let day = SolarDay::new(SYDNEY.0, SYDNEY.1, date(2024, 12, 21), 11 * 3600);
+
+        assert_close(day.sunrise(), time(5, 41));
+        assert_close(day.sunset(), time(20, 5));
+    }
+
+    #[test]
+    fn twilight_stages_are_ordered() {
+        let day = SolarDay::new(LONDON.0, LONDON.1, date(2024, 3, 20), 0);
+
+        let astronomical = day.dawn(Twilight::Astronomical).unwrap();
+        let nautical = day.dawn(Twilight::Nautical).unwrap();
+        let civil = day.dawn(Twilight::Civil).unwrap();
+        let sunrise = day.sunrise().unwrap();
+
+        assert!(astronomical < nautical && nautical < civil && civil < sunrise);
+        assert!(day.sunset().unwrap() < day.dusk(Twilight::Civil).unwrap());
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let midsummer = SolarDay::new(TROMSO.0, TROMSO.1, date(2024, 6, 21), 7200);
+        let midwinter = SolarDay::new(TROMSO.0, TROMSO.1, date(2024, 12, 21), 3600);
+
+        assert_eq!(midsummer.sunrise(), None);
+        assert_eq!(midwinter.sunrise(), None);
+        assert!(midwinter.dawn(Twilight::Civil).is_some());
+    }
+
+    #[test]
+    fn moon_phase_and_illumination() {
+        // new moon of the 2024-04-08 total solar eclipse
+        let new_moon = Moon::new(date(2024, 4, 8), 0);
+        assert_eq!(new_moon.phase, MoonPhase::NewMoon);
+        assert!(new_moon.illumination < 2.0);
+
+        // full moon at 2024-04-23 23:49 UTC
+        let full_moon = Moon::new(date(2024, 4, 24), 0);
+        assert_eq!(full_moon.phase, MoonPhase::FullMoon);
+        assert!(full_moon.illumination > 98.0);
+
+        // first quarter on 2024-04-15
+        let first_quarter = Moon::new(date(2024, 4, 15), 0);
+        assert_eq!(first_quarter.phase, MoonPhase::FirstQuarter);
+        assert!((first_quarter.illumination - 50.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn moon_phase_names() {
+        assert_eq!(
+            MoonPhase::from_name("Waxing Gibbous"),
+            Some(MoonPhase::WaxingGibbous)
+        );
+        assert_eq!(MoonPhase::from_name("Blue Moon"), None);
+    }
+}
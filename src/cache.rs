@@ -0,0 +1,183 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    api::{get_weather, search, ApiError, Query, SearchResult},
+    weather::Weather,
+};
+
+/// How long fetched weather is reused and where it is kept
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// weatherapi.com updates current conditions every 15 minutes
+    pub ttl_minutes: u64,
+    /// Defaults to $XDG_CACHE_HOME/t-weather, falling back to ~/.cache
+    pub dir: Option<PathBuf>,
+}
+
+/// Weather and search responses saved on disk, so repeated requests within the TTL don't reach the API
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: Option<PathBuf>,
+    ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl_minutes: 15,
+            dir: None,
+        }
+    }
+}
+
+impl Cache {
+    pub fn new(config: &CacheConfig) -> Cache {
+        Cache {
+            dir: config.dir.clone().or_else(default_dir),
+            ttl: Duration::from_secs(config.ttl_minutes * 60),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Weather from the cache if it was saved within the TTL, otherwise fetched and saved
+    pub fn get_weather(&self, location: &str, query: &Query) -> Result<Weather, ApiError> {
+        self.get_or_fetch(self.path(location, &query.cache_key()), || {
+            get_weather(location, query)
+        })
+    }
+
+    /// Search results from the cache if they were saved within the TTL, otherwise fetched and saved
+    pub fn search(&self, text: &str) -> Result<Vec<SearchResult>, ApiError> {
+        self.get_or_fetch(self.path(text, "search"), || search(text))
+    }
+
+    fn get_or_fetch<T: Serialize + DeserializeOwned>(
+        &self,
+        path: Option<PathBuf>,
+        fetch: impl FnOnce() -> Result<T, ApiError>,
+    ) -> Result<T, ApiError> {
+        if let Some(value) = path.as_deref().and_then(|path| self.read(path)) {
+            return Ok(value);
+        }
+
+        let value = fetch()?;
+        // the cache is only an optimisation, so failing to save is not an error
+        if let Some(path) = path {
+            let _ = write(&path, &value);
+        }
+
+        Ok(value)
+    }
+
+    /// Saves weather for the location as if it had just been fetched
+    #[cfg(test)]
+    pub(crate) fn seed(&self, location: &str, query: &Query, weather: &Weather) {
+        let path = self.path(location, &query.cache_key()).unwrap();
+        write(&path, weather).unwrap();
+    }
+
+    /// File for the location and request. Characters that aren't safe in file names are
+    /// percent-encoded, so different locations never share a file
+    fn path(&self, location: &str, key: &str) -> Option<PathBuf> {
+        let mut name = String::new();
+        for c in format!("{}-{}", location.to_lowercase(), key).chars() {
+            match c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '=') {
+                true => name.push(c),
+                false => {
+                    for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                        name.push_str(&format!("%{:02X}", byte));
+                    }
+                }
+            }
+        }
+
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", name)))
+    }
+
+    fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        let age = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        if age >= self.ttl {
+            return None;
+        }
+
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+}
+
+fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(value)?)?;
+
+    Ok(())
+}
+
+fn default_dir() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_dir.join("t-weather"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    fn cache(name: &str, ttl_minutes: u64) -> Cache {
+        let dir = env::temp_dir().join(format!("t-weather-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        Cache::new(&CacheConfig {
+            ttl_minutes,
+            dir: Some(dir),
+        })
+    }
+
+    #[test]
+    fn reads_saved_weather_within_ttl() {
+        let weather = fixture_weather();
+        let query = Query::Forecast(2);
+
+        let cache = cache("fresh", 15);
+        let path = cache.path("London, UK", &query.cache_key()).unwrap();
+        assert!(path.ends_with("london%2C%20uk-forecast.json-days=2-alerts=yes-aqi=yes.json"));
+        assert!(cache.read::<Weather>(&path).is_none());
+
+        write(&path, &weather).unwrap();
+        assert_eq!(cache.read(&path), Some(weather));
+
+        // nothing is fresh with a TTL of zero
+        assert!(self::cache("expired", 0).read::<Weather>(&path).is_none());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn different_locations_use_different_files() {
+        let cache = cache("names", 15);
+        let key = Query::Forecast(1).cache_key();
+
+        for (first, second) in [("北京", "東京"), ("London, UK", "London_ UK")] {
+            assert_ne!(cache.path(first, &key), cache.path(second, &key));
+        }
+        // the API matches locations case-insensitively, so these can share
+        assert_eq!(cache.path("LONDON", &key), cache.path("london", &key));
+    }
+}
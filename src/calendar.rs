@@ -0,0 +1,262 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::{
+    summary::{summarize_day, Language},
+    utils::slug,
+    weather::{Forecastday, Weather},
+};
+
+const PRODID: &str = "-//t-weather//Forecast//EN";
+/// Longest content line allowed by RFC 5545, in bytes, before it has to be folded
+const MAX_LINE_BYTES: usize = 75;
+/// How often calendar apps are asked to refetch a subscribed feed
+const REFRESH_INTERVAL: &str = "PT1H";
+
+/// iCalendar with an all-day event for each forecast day, and timed events for sunrise, sunset
+/// and active alerts. Local times are converted to UTC in the location's time zone, so events
+/// after a DST change keep their local time
+pub fn render_calendar(weather: &Weather, language: Language, stamp: DateTime<Utc>) -> String {
+    let location = slug(&weather.location.name);
+    let stamp = format_utc(stamp);
+
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        format!("PRODID:{}", PRODID),
+        String::from("CALSCALE:GREGORIAN"),
+        format!(
+            "X-WR-CALNAME:{}",
+            escape(&format!("Weather {}", weather.location.name))
+        ),
+        format!("REFRESH-INTERVAL;VALUE=DURATION:{}", REFRESH_INTERVAL),
+        format!("X-PUBLISHED-TTL:{}", REFRESH_INTERVAL),
+    ];
+
+    for day in &weather.forecast.forecastday {
+        lines.extend([
+            String::from("BEGIN:VEVENT"),
+            format!(
+                "UID:day-{}-{}@t-weather",
+                day.date.format("%Y%m%d"),
+                location
+            ),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", format_date(day.date)),
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                format_date(day.date + Duration::days(1))
+            ),
+            format!("SUMMARY:{}", escape(&day_summary(day))),
+            format!("DESCRIPTION:{}", escape(&summarize_day(day, language))),
+            String::from("TRANSP:TRANSPARENT"),
+            String::from("END:VEVENT"),
+        ]);
+
+        let sun = [
+            ("sunrise", "🌅 Sunrise", day.astro.sunrise),
+            ("sunset", "🌇 Sunset", day.astro.sunset),
+        ];
+        for (kind, summary, time) in sun {
+            let Some(time) =
+                time.and_then(|time| weather.location.localize(day.date.and_time(time)))
+            else {
+                continue;
+            };
+
+            lines.extend([
+                String::from("BEGIN:VEVENT"),
+                format!(
+                    "UID:{}-{}-{}@t-weather",
+                    kind,
+                    day.date.format("%Y%m%d"),
+                    location
+                ),
+                format!("DTSTAMP:{}", stamp),
+                format!("DTSTART:{}", format_utc(time.to_utc())),
+                format!("SUMMARY:{}", escape(summary)),
+                String::from("TRANSP:TRANSPARENT"),
+                String::from("END:VEVENT"),
+            ]);
+        }
+    }
+
+    for alert in &weather.alerts.alert {
+        // without a start there is nothing to place on the calendar
+        let Some(effective) = alert.effective else {
+            continue;
+        };
+
+        lines.extend([
+            String::from("BEGIN:VEVENT"),
+            format!(
+                "UID:alert-{}-{}-{}@t-weather",
+                slug(&alert.event),
+                effective.timestamp(),
+                location
+            ),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", format_utc(effective.to_utc())),
+        ]);
+        if let Some(expires) = alert.expires {
+            lines.push(format!("DTEND:{}", format_utc(expires.to_utc())));
+        }
+        lines.extend([
+            format!("SUMMARY:{}", escape(&format!("⚠ {}", alert.event))),
+            format!("DESCRIPTION:{}", escape(&alert.desc)),
+            String::from("END:VEVENT"),
+        ]);
+    }
+
+    lines.push(String::from("END:VCALENDAR"));
+
+    let mut calendar = String::new();
+    for line in lines {
+        let _ = write!(calendar, "{}\r\n", fold(&line));
+    }
+
+    calendar
+}
+
+/// e.g. "☔ 14°/7° Heavy rain"
+fn day_summary(day: &Forecastday) -> String {
+    format!(
+        "{} {}°/{}° {}",
+        day.day.condition.kind().icon(),
+        day.day.maxtemp_c.round(),
+        day.day.mintemp_c.round(),
+        day.day.condition.text.trim()
+    )
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn format_utc(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters with a meaning in TEXT values
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits a content line into lines of at most 75 bytes, continuations start with a space.
+/// Characters are never split, so multi-byte characters stay valid UTF-8
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_BYTES {
+            folded.push_str("\r\n ");
+            // the leading space counts towards the continuation's length
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn renders_events_for_each_day() {
+        let weather = fixture_weather();
+        let stamp = DateTime::from_timestamp(1714550400, 0).unwrap();
+
+        let calendar = render_calendar(&weather, Language::En, stamp);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar
+            .split("\r\n")
+            .all(|line| line.len() <= MAX_LINE_BYTES));
+
+        let days = weather.forecast.forecastday.len();
+        assert_eq!(calendar.matches("DTSTART;VALUE=DATE:").count(), days);
+        let first = &weather.forecast.forecastday[0];
+        assert!(calendar.contains(&format!("SUMMARY:{}", escape(&day_summary(first)))));
+        assert!(calendar.contains("DTSTAMP:20240501T080000Z"));
+
+        let sun = weather
+            .forecast
+            .forecastday
+            .iter()
+            .map(|day| day.astro.sunrise.iter().count() + day.astro.sunset.iter().count())
+            .sum::<usize>();
+        let alerts = weather
+            .alerts
+            .alert
+            .iter()
+            .filter(|alert| alert.effective.is_some())
+            .count();
+        assert_eq!(
+            calendar.matches("BEGIN:VEVENT").count(),
+            days + sun + alerts
+        );
+    }
+
+    #[test]
+    fn gives_non_ascii_locations_their_own_uids() {
+        let stamp = DateTime::from_timestamp(1714550400, 0).unwrap();
+        let uids = |name: &str| -> Vec<String> {
+            let mut weather = fixture_weather();
+            weather.location.name = name.to_string();
+            render_calendar(&weather, Language::En, stamp)
+                .split("\r\n")
+                .filter(|line| line.starts_with("UID:"))
+                .map(String::from)
+                .collect()
+        };
+
+        let beijing = uids("北京");
+        let tokyo = uids("東京");
+        assert!(beijing[0].contains("-e58c97e4baac@"), "{}", beijing[0]);
+        assert!(beijing.iter().all(|uid| !tokyo.contains(uid)));
+    }
+
+    #[test]
+    fn converts_sun_times_across_daylight_saving() {
+        let mut weather = fixture_weather();
+        // London either side of the clocks going forward on 2024-03-31
+        for (day, date) in weather
+            .forecast
+            .forecastday
+            .iter_mut()
+            .zip(["2024-03-30", "2024-03-31"])
+        {
+            day.date = date.parse().unwrap();
+            day.astro.sunrise = NaiveTime::from_hms_opt(6, 38, 0);
+        }
+        let stamp = DateTime::from_timestamp(1714550400, 0).unwrap();
+
+        let calendar = render_calendar(&weather, Language::En, stamp);
+        assert!(calendar.contains("DTSTART:20240330T063800Z"));
+        assert!(calendar.contains("DTSTART:20240331T053800Z"));
+    }
+
+    #[test]
+    fn folds_long_lines_between_characters() {
+        let line = format!("DESCRIPTION:{}", "☔".repeat(40));
+        let folded = fold(&line);
+
+        assert!(folded
+            .split("\r\n")
+            .all(|part| part.len() <= MAX_LINE_BYTES));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+        assert_eq!(escape("a, b; c\nd"), "a\\, b\\; c\\nd");
+    }
+}
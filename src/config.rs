@@ -0,0 +1,135 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    activity::ActivityProfile, advice::AdviceConfig, cache::CacheConfig, day_parts::DayPart,
+    mqtt::MqttConfig, recorder::RecorderConfig, serve::ServeConfig, summary::Language,
+    watch::WatchConfig,
+};
+
+/// Settings read from config.toml, every section is optional
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Hour ranges the day-part tables are split into
+    pub day_parts: Vec<DayPart>,
+    /// Language of the daily summaries, overridden by --lang
+    pub language: Language,
+    /// Time windows and thresholds for the `advice` subcommand and panel
+    pub advice: AdviceConfig,
+    /// Profiles for the `activity` subcommand and panel, on top of the built-in ones
+    #[serde(deserialize_with = "ActivityProfile::deserialize_with_defaults")]
+    pub activities: Vec<ActivityProfile>,
+    /// Locations and rules for the `watch` subcommand
+    pub watch: WatchConfig,
+    /// How long fetched weather is reused by `serve` and `mqtt`
+    pub cache: CacheConfig,
+    /// Address and locations for the `serve` subcommand
+    pub serve: ServeConfig,
+    /// Broker, topics and locations for the `mqtt` subcommand
+    pub mqtt: MqttConfig,
+    /// Local database of every observation and forecast fetched
+    pub recorder: RecorderConfig,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            day_parts: DayPart::defaults(),
+            language: Language::default(),
+            advice: AdviceConfig::default(),
+            activities: ActivityProfile::defaults(),
+            watch: WatchConfig::default(),
+            cache: CacheConfig::default(),
+            serve: ServeConfig::default(),
+            mqtt: MqttConfig::default(),
+            recorder: RecorderConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from the given path, or from the default location if none is given
+    /// A missing file at the default location gives the default config
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        let config: Config =
+            toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        config
+            .validate()
+            .map_err(|e| ConfigError::Invalid(path, e))?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for part in self.day_parts.iter().chain(&self.advice.windows) {
+            if part.start > 23 || part.end > 24 {
+                return Err(format!(
+                    "'{}' must start between 0 and 23 and end between 0 and 24",
+                    part.name
+                ));
+            }
+        }
+
+        for activity in &self.activities {
+            if !(1..=24).contains(&activity.hours) {
+                return Err(format!(
+                    "activity '{}' must last between 1 and 24 hours",
+                    activity.name
+                ));
+            }
+        }
+
+        self.watch.validate(&self.day_parts)?;
+        self.serve.validate()?;
+        self.mqtt.validate()?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "Unable to read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "Invalid config {}: {}", path.display(), e),
+            ConfigError::Invalid(path, e) => write!(f, "Invalid config {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// $XDG_CONFIG_HOME/t-weather/config.toml, falling back to ~/.config
+fn default_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("t-weather").join("config.toml"))
+}
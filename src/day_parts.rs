@@ -0,0 +1,227 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Deserialize;
+
+use crate::weather::{Condition, Weather};
+
+/// Named range of hours within a day, e.g. Morning from 06:00 to 12:00
+/// Ranges that end at or before their start continue into the next day, e.g. Night 22-6
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DayPart {
+    pub name: String,
+    /// First hour included (0-23)
+    pub start: u32,
+    /// Hour the range ends at, exclusive (0-24)
+    pub end: u32,
+}
+
+/// Weather aggregated over every hour of a day part
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayPartSummary<'a> {
+    pub part: &'a DayPart,
+    pub min_temp_c: f64,
+    pub max_temp_c: f64,
+    pub min_temp_f: f64,
+    pub max_temp_f: f64,
+    /// Condition reported for the most hours, the earliest wins a tie
+    pub condition: &'a Condition,
+    pub max_chance_of_rain: i64,
+    pub max_gust_kph: f64,
+    /// Some hours of the part weren't fetched, e.g. the night after the last forecast day
+    pub partial: bool,
+}
+
+impl DayPart {
+    pub fn new(name: &str, start: u32, end: u32) -> DayPart {
+        DayPart {
+            name: name.to_string(),
+            start,
+            end,
+        }
+    }
+
+    pub fn defaults() -> Vec<DayPart> {
+        vec![
+            DayPart::new("Morning", 6, 12),
+            DayPart::new("Afternoon", 12, 18),
+            DayPart::new("Evening", 18, 22),
+            DayPart::new("Night", 22, 6),
+        ]
+    }
+
+    /// Local times covered by the day part on the given date, end exclusive
+    pub fn range(&self, date: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+        let start = date.and_time(NaiveTime::MIN) + Duration::hours(self.start as i64);
+        let mut end = date.and_time(NaiveTime::MIN) + Duration::hours(self.end as i64);
+
+        if end <= start {
+            end += Duration::days(1);
+        }
+
+        (start, end)
+    }
+
+    /// Occurrence of the day part in progress at `now`, or else the next one. A part that runs
+    /// past midnight may be in progress from yesterday, e.g. Night 22-6 at 03:00
+    pub fn next_range(&self, now: NaiveDateTime) -> (NaiveDateTime, NaiveDateTime) {
+        let yesterday = self.range(now.date() - Duration::days(1));
+        if yesterday.1 > now {
+            return yesterday;
+        }
+
+        let today = self.range(now.date());
+        match today.1 <= now {
+            true => self.range(now.date() + Duration::days(1)),
+            false => today,
+        }
+    }
+
+    /// Hour range in the form 06-12
+    pub fn label(&self) -> String {
+        format!("{:02}-{:02}", self.start, self.end)
+    }
+}
+
+/// Summarises each day part of the given date, skipping parts without any hourly data and
+/// flagging those with only some of it
+pub fn summarize<'a>(
+    weather: &'a Weather,
+    date: NaiveDate,
+    parts: &'a [DayPart],
+) -> Vec<DayPartSummary<'a>> {
+    parts
+        .iter()
+        .filter_map(|part| {
+            let (start, end) = part.range(date);
+            let hours: Vec<_> = weather.hours_between(start, end).collect();
+            let first = hours.first()?;
+
+            let mut summary = DayPartSummary {
+                part,
+                min_temp_c: first.temp_c,
+                max_temp_c: first.temp_c,
+                min_temp_f: first.temp_f,
+                max_temp_f: first.temp_f,
+                condition: &first.condition,
+                max_chance_of_rain: first.chance_of_rain,
+                max_gust_kph: first.gust_kph,
+                partial: (hours.len() as i64) < (end - start).num_hours(),
+            };
+
+            for hour in &hours {
+                summary.min_temp_c = summary.min_temp_c.min(hour.temp_c);
+                summary.max_temp_c = summary.max_temp_c.max(hour.temp_c);
+                summary.min_temp_f = summary.min_temp_f.min(hour.temp_f);
+                summary.max_temp_f = summary.max_temp_f.max(hour.temp_f);
+                summary.max_chance_of_rain = summary.max_chance_of_rain.max(hour.chance_of_rain);
+                summary.max_gust_kph = summary.max_gust_kph.max(hour.gust_kph);
+            }
+
+            let mut best_count = 0;
+            for hour in &hours {
+                let count = hours
+                    .iter()
+                    .filter(|other| other.condition.code == hour.condition.code)
+                    .count();
+
+                if count > best_count {
+                    best_count = count;
+                    summary.condition = &hour.condition;
+                }
+            }
+
+            Some(summary)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    fn datetime(day: u32, hour: u32) -> NaiveDateTime {
+        date(day).and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn ranges_wrap_into_next_day() {
+        let morning = DayPart::new("Morning", 6, 12);
+        assert_eq!(morning.range(date(1)), (datetime(1, 6), datetime(1, 12)));
+
+        let night = DayPart::new("Night", 22, 6);
+        assert_eq!(night.range(date(1)), (datetime(1, 22), datetime(2, 6)));
+        // a part ending at 24 ends at midnight, one with the same start and end lasts a day
+        let evening = DayPart::new("Evening", 18, 24);
+        assert_eq!(evening.range(date(1)).1, datetime(2, 0));
+        let all_day = DayPart::new("All day", 0, 0);
+        assert_eq!(all_day.range(date(1)), (datetime(1, 0), datetime(2, 0)));
+
+        // today's part until it has ended, then tomorrow's
+        assert_eq!(night.next_range(datetime(2, 23)).0, datetime(2, 22));
+        assert_eq!(morning.next_range(datetime(2, 11)).0, datetime(2, 6));
+        assert_eq!(morning.next_range(datetime(2, 12)).0, datetime(3, 6));
+        // the night that began yesterday is still in progress at 03:00, and over at 06:00
+        assert_eq!(
+            night.next_range(datetime(2, 3)),
+            (datetime(1, 22), datetime(2, 6))
+        );
+        assert_eq!(night.next_range(datetime(2, 6)).0, datetime(2, 22));
+        assert_eq!(all_day.next_range(datetime(2, 3)).0, datetime(2, 0));
+        assert_eq!(morning.label(), "06-12");
+    }
+
+    #[test]
+    fn summarizes_hours_of_each_part() {
+        let weather = fixture_weather();
+        let parts = DayPart::defaults();
+
+        let summaries = summarize(&weather, date(1), &parts);
+        assert_eq!(summaries.len(), parts.len());
+        assert!(summaries.iter().all(|summary| !summary.partial));
+
+        for (summary, part) in summaries.iter().zip(&parts) {
+            let (start, end) = part.range(date(1));
+            let hours: Vec<_> = weather.hours_between(start, end).collect();
+            let max_temp = hours
+                .iter()
+                .map(|hour| hour.temp_c)
+                .fold(f64::MIN, f64::max);
+            let min_temp = hours
+                .iter()
+                .map(|hour| hour.temp_c)
+                .fold(f64::MAX, f64::min);
+            let max_rain = hours.iter().map(|hour| hour.chance_of_rain).max();
+
+            assert_eq!(summary.part, part);
+            assert_eq!(
+                (summary.min_temp_c, summary.max_temp_c),
+                (min_temp, max_temp)
+            );
+            assert_eq!(Some(summary.max_chance_of_rain), max_rain);
+        }
+
+        // the night runs into the second day's early hours
+        let night = &summaries[3];
+        let early = weather.hour_at(datetime(2, 5)).unwrap();
+        assert!(night.max_gust_kph >= early.gust_kph);
+    }
+
+    #[test]
+    fn flags_parts_past_the_last_day() {
+        let weather = fixture_weather();
+        let parts = DayPart::defaults();
+
+        // the forecast ends at midnight, two hours into the last night
+        let summaries = summarize(&weather, date(2), &parts);
+        assert_eq!(summaries.len(), parts.len());
+        assert!(summaries[..3].iter().all(|summary| !summary.partial));
+        assert_eq!(summaries[3].part.name, "Night");
+        assert!(summaries[3].partial);
+
+        assert!(summarize(&weather, date(3), &parts).is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+
+/// Lower bounds (km/h) of Beaufort forces 1 to 12
+const BEAUFORT_BOUNDS_KPH: [f64; 12] = [
+    1.0, 6.0, 12.0, 20.0, 29.0, 39.0, 50.0, 62.0, 75.0, 89.0, 103.0, 118.0,
+];
+const BEAUFORT_DESCRIPTIONS: [&str; 13] = [
+    "Calm",
+    "Light air",
+    "Light breeze",
+    "Gentle breeze",
+    "Moderate breeze",
+    "Fresh breeze",
+    "Strong breeze",
+    "Near gale",
+    "Gale",
+    "Strong gale",
+    "Storm",
+    "Violent storm",
+    "Hurricane force",
+];
+
+/// Height (m) the cloud base rises per degree of spread between temperature and dew point
+const CLOUD_BASE_M_PER_DEGREE: f64 = 125.0;
+
+/// Pressure change (hPa) over three hours below which the pressure is considered steady
+const STEADY_PRESSURE_HPA: f64 = 1.0;
+/// Hours the pressure tendency is measured over
+pub const PRESSURE_TENDENCY_HOURS: i64 = 3;
+
+/// Values computed from the temperature, humidity, wind and dew point reported by the provider
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Derived {
+    /// Apparent temperature (C), Australian Bureau of Meteorology formula
+    pub apparent_temp_c: f64,
+    pub wet_bulb_c: f64,
+    /// Water vapour per volume of air (g/m3)
+    pub absolute_humidity: f64,
+    /// Estimated height of the cloud base above ground (m)
+    pub cloud_base_m: f64,
+    pub beaufort: Beaufort,
+    pub thermal_stress: ThermalStress,
+}
+
+/// Beaufort wind force, 0 (calm) to 12 (hurricane force)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Beaufort(pub u8);
+
+/// Heat or cold stress category, using the UTCI assessment scale on the apparent temperature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermalStress {
+    ExtremeCold,
+    VeryStrongCold,
+    StrongCold,
+    ModerateCold,
+    SlightCold,
+    None,
+    ModerateHeat,
+    StrongHeat,
+    VeryStrongHeat,
+    ExtremeHeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureTendency {
+    Rising,
+    Steady,
+    Falling,
+}
+
+impl Derived {
+    pub fn new(temp_c: f64, humidity: f64, wind_kph: f64, dewpoint_c: f64) -> Derived {
+        let apparent_temp_c = apparent_temperature(temp_c, humidity, wind_kph);
+
+        Derived {
+            apparent_temp_c,
+            wet_bulb_c: wet_bulb(temp_c, humidity),
+            absolute_humidity: absolute_humidity(temp_c, humidity),
+            cloud_base_m: cloud_base(temp_c, dewpoint_c),
+            beaufort: Beaufort::from_kph(wind_kph),
+            thermal_stress: ThermalStress::from_temp(apparent_temp_c),
+        }
+    }
+}
+
+impl Beaufort {
+    pub fn from_kph(kph: f64) -> Beaufort {
+        Beaufort(
+            BEAUFORT_BOUNDS_KPH
+                .iter()
+                .filter(|bound| kph >= **bound)
+                .count() as u8,
+        )
+    }
+
+    pub fn description(&self) -> &'static str {
+        BEAUFORT_DESCRIPTIONS[self.0 as usize]
+    }
+}
+
+impl fmt::Display for Beaufort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.0, self.description())
+    }
+}
+
+impl ThermalStress {
+    pub fn from_temp(temp_c: f64) -> ThermalStress {
+        match temp_c {
+            t if t > 46.0 => ThermalStress::ExtremeHeat,
+            t if t > 38.0 => ThermalStress::VeryStrongHeat,
+            t if t > 32.0 => ThermalStress::StrongHeat,
+            t if t > 26.0 => ThermalStress::ModerateHeat,
+            t if t >= 9.0 => ThermalStress::None,
+            t if t >= 0.0 => ThermalStress::SlightCold,
+            t if t >= -13.0 => ThermalStress::ModerateCold,
+            t if t >= -27.0 => ThermalStress::StrongCold,
+            t if t >= -40.0 => ThermalStress::VeryStrongCold,
+            _ => ThermalStress::ExtremeCold,
+        }
+    }
+}
+
+impl fmt::Display for ThermalStress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ThermalStress::ExtremeCold => "Extreme cold stress",
+            ThermalStress::VeryStrongCold => "Very strong cold stress",
+            ThermalStress::StrongCold => "Strong cold stress",
+            ThermalStress::ModerateCold => "Moderate cold stress",
+            ThermalStress::SlightCold => "Slight cold stress",
+            ThermalStress::None => "No thermal stress",
+            ThermalStress::ModerateHeat => "Moderate heat stress",
+            ThermalStress::StrongHeat => "Strong heat stress",
+            ThermalStress::VeryStrongHeat => "Very strong heat stress",
+            ThermalStress::ExtremeHeat => "Extreme heat stress",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl PressureTendency {
+    /// Tendency from the pressure change (hPa) over the last three hours
+    pub fn from_change(change_hpa: f64) -> PressureTendency {
+        if change_hpa >= STEADY_PRESSURE_HPA {
+            PressureTendency::Rising
+        } else if change_hpa <= -STEADY_PRESSURE_HPA {
+            PressureTendency::Falling
+        } else {
+            PressureTendency::Steady
+        }
+    }
+}
+
+impl fmt::Display for PressureTendency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PressureTendency::Rising => "Rising",
+            PressureTendency::Steady => "Steady",
+            PressureTendency::Falling => "Falling",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Water vapour pressure (hPa) for the temperature and relative humidity
+fn vapour_pressure(temp_c: f64, humidity: f64) -> f64 {
+    humidity / 100.0 * 6.105 * (17.27 * temp_c / (237.7 + temp_c)).exp()
+}
+
+/// Australian Bureau of Meteorology apparent temperature, without the radiation term
+pub fn apparent_temperature(temp_c: f64, humidity: f64, wind_kph: f64) -> f64 {
+    let wind_ms = wind_kph / 3.6;
+
+    temp_c + 0.33 * vapour_pressure(temp_c, humidity) - 0.70 * wind_ms - 4.00
+}
+
+/// Wet-bulb temperature using Stull's (2011) approximation, valid for 5-99% humidity
+pub fn wet_bulb(temp_c: f64, humidity: f64) -> f64 {
+    temp_c * (0.151977 * (humidity + 8.313659).sqrt()).atan() + (temp_c + humidity).atan()
+        - (humidity - 1.676331).atan()
+        + 0.00391838 * humidity.powf(1.5) * (0.023101 * humidity).atan()
+        - 4.686035
+}
+
+/// Absolute humidity (g/m3) for the temperature and relative humidity
+pub fn absolute_humidity(temp_c: f64, humidity: f64) -> f64 {
+    let saturation = 6.112 * (17.67 * temp_c / (temp_c + 243.5)).exp();
+
+    saturation * humidity * 2.1674 / (273.15 + temp_c)
+}
+
+/// Cloud base above ground (m), estimated from the spread between temperature and dew point
+pub fn cloud_base(temp_c: f64, dewpoint_c: f64) -> f64 {
+    ((temp_c - dewpoint_c) * CLOUD_BASE_M_PER_DEGREE).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} to be within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn wet_bulb_matches_stull_example() {
+        // worked example from Stull (2011)
+        assert_close(wet_bulb(20.0, 50.0), 13.7, 0.1);
+    }
+
+    #[test]
+    fn apparent_temperature_accounts_for_humidity_and_wind() {
+        // 25C at 50% humidity has a vapour pressure of about 15.8 hPa
+        assert_close(apparent_temperature(25.0, 50.0, 0.0), 26.2, 0.1);
+        assert!(apparent_temperature(25.0, 50.0, 36.0) < apparent_temperature(25.0, 50.0, 0.0));
+    }
+
+    #[test]
+    fn absolute_humidity_at_saturation() {
+        // saturated air at 20C holds about 17.3 g/m3
+        assert_close(absolute_humidity(20.0, 100.0), 17.3, 0.1);
+    }
+
+    #[test]
+    fn cloud_base_from_dew_point_spread() {
+        assert_close(cloud_base(15.0, 7.0), 1000.0, 0.001);
+        assert_close(cloud_base(10.0, 10.5), 0.0, 0.001);
+    }
+
+    #[test]
+    fn beaufort_scale() {
+        assert_eq!(Beaufort::from_kph(0.0), Beaufort(0));
+        assert_eq!(Beaufort::from_kph(19.9), Beaufort(3));
+        assert_eq!(Beaufort::from_kph(20.0), Beaufort(4));
+        assert_eq!(Beaufort::from_kph(55.0).description(), "Near gale");
+        assert_eq!(Beaufort::from_kph(150.0), Beaufort(12));
+    }
+
+    #[test]
+    fn thermal_stress_categories() {
+        assert_eq!(ThermalStress::from_temp(20.0), ThermalStress::None);
+        assert_eq!(ThermalStress::from_temp(35.0), ThermalStress::StrongHeat);
+        assert_eq!(ThermalStress::from_temp(-20.0), ThermalStress::StrongCold);
+        assert_eq!(
+            PressureTendency::from_change(-2.5),
+            PressureTendency::Falling
+        );
+        assert_eq!(PressureTendency::from_change(0.4), PressureTendency::Steady);
+    }
+}
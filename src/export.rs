@@ -0,0 +1,349 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use chrono::Utc;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::{calendar::render_calendar, summary::Language, weather::Weather};
+
+/// Metric and imperial suffixes of fields reported in both, e.g. temp_c and temp_f
+const UNIT_PAIRS: [(&str, &str); 6] = [
+    ("_c", "_f"),
+    ("_kph", "_mph"),
+    ("_mm", "_in"),
+    ("_mb", "_in"),
+    ("_km", "_miles"),
+    ("_mt", "_ft"),
+];
+
+/// Units of the exported fields, fields reported in only one unit are always included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values, with a header row unless --no-header is given
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+    /// iCalendar events for each day, sunrise, sunset and alert, chosen with --ics
+    #[value(skip)]
+    Ics,
+}
+
+/// What `export` writes and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub units: Units,
+    /// All columns when empty
+    pub columns: Vec<String>,
+    pub header: bool,
+    /// Stdout when not set
+    pub path: Option<PathBuf>,
+}
+
+/// Hourly forecast flattened into rows, one per hour
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// Column requested with --columns that the data doesn't have, with the ones it does
+    UnknownColumn(String, Vec<String>),
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::UnknownColumn(column, available) => write!(
+                f,
+                "Unknown column {}, available columns are: {}",
+                column,
+                available.join(", ")
+            ),
+            ExportError::Io(e) => write!(f, "Unable to write export: {}", e),
+            ExportError::Csv(e) => write!(f, "Unable to write CSV: {}", e),
+            ExportError::Json(e) => write!(f, "Unable to write JSON: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<csv::Error> for ExportError {
+    fn from(e: csv::Error) -> Self {
+        ExportError::Csv(e)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        ExportError::Json(e)
+    }
+}
+
+impl Table {
+    /// Every hour of the forecast with the location, local time, condition and each numeric field
+    /// of `Hour` in the chosen units, in the order they are declared
+    pub fn hourly(weather: &Weather, units: Units) -> Result<Table, ExportError> {
+        let mut columns = vec![
+            String::from("location"),
+            String::from("time"),
+            String::from("condition"),
+        ];
+        let mut records: Vec<Map<String, Value>> = vec![];
+
+        for hour in weather.hours() {
+            let Value::Object(fields) = serde_json::to_value(hour)? else {
+                continue;
+            };
+
+            let mut record = Map::new();
+            record.insert(
+                String::from("location"),
+                Value::from(weather.location.name.as_str()),
+            );
+            record.insert(
+                String::from("time"),
+                Value::from(hour.time.format("%Y-%m-%d %H:%M").to_string()),
+            );
+            record.insert(
+                String::from("condition"),
+                Value::from(hour.condition.text.as_str()),
+            );
+            for (name, value) in &fields {
+                if value.is_number() && in_units(name, &fields, units) {
+                    record.insert(name.clone(), value.clone());
+                }
+            }
+
+            // marine fields are only present for some hours, so columns are collected across all of them
+            for name in record.keys() {
+                if !columns.contains(name) {
+                    columns.push(name.clone());
+                }
+            }
+            records.push(record);
+        }
+
+        let rows = records
+            .iter()
+            .map(|record| {
+                columns
+                    .iter()
+                    .map(|column| record.get(column).cloned().unwrap_or(Value::Null))
+                    .collect()
+            })
+            .collect();
+
+        Ok(Table { columns, rows })
+    }
+
+    /// Only the given columns, in the given order
+    pub fn select(self, columns: &[String]) -> Result<Table, ExportError> {
+        let mut indices = vec![];
+        for column in columns {
+            match self.columns.iter().position(|name| name == column) {
+                Some(index) => indices.push(index),
+                None => {
+                    return Err(ExportError::UnknownColumn(
+                        column.clone(),
+                        self.columns.clone(),
+                    ))
+                }
+            }
+        }
+
+        Ok(Table {
+            columns: columns.to_vec(),
+            rows: self
+                .rows
+                .into_iter()
+                .map(|row| indices.iter().map(|&index| row[index].clone()).collect())
+                .collect(),
+        })
+    }
+
+    pub fn write_csv(&self, writer: impl Write, header: bool) -> Result<(), ExportError> {
+        let mut csv = csv::Writer::from_writer(writer);
+        if header {
+            csv.write_record(&self.columns)?;
+        }
+        for row in &self.rows {
+            csv.write_record(row.iter().map(|value| match value {
+                Value::String(text) => text.clone(),
+                Value::Null => String::new(),
+                value => value.to_string(),
+            }))?;
+        }
+        csv.flush()?;
+
+        Ok(())
+    }
+
+    pub fn write_ndjson(&self, mut writer: impl Write) -> Result<(), ExportError> {
+        for row in &self.rows {
+            let object: Map<String, Value> = self
+                .columns
+                .iter()
+                .cloned()
+                .zip(row.iter().cloned())
+                .collect();
+            serde_json::to_writer(&mut writer, &object)?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Writes the weather in the format, units and columns chosen
+pub fn write_export(
+    weather: &Weather,
+    options: &ExportOptions,
+    language: Language,
+    mut writer: impl Write,
+) -> Result<(), ExportError> {
+    match options.format {
+        ExportFormat::Csv => selected_table(weather, options)?.write_csv(writer, options.header),
+        ExportFormat::Ndjson => selected_table(weather, options)?.write_ndjson(writer),
+        ExportFormat::Ics => {
+            writer.write_all(render_calendar(weather, language, Utc::now()).as_bytes())?;
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn selected_table(weather: &Weather, options: &ExportOptions) -> Result<Table, ExportError> {
+    let table = Table::hourly(weather, options.units)?;
+
+    match options.columns.is_empty() {
+        true => Ok(table),
+        false => table.select(&options.columns),
+    }
+}
+
+/// Whether the field is in the chosen units, or only reported in one
+fn in_units(name: &str, fields: &Map<String, Value>, units: Units) -> bool {
+    UNIT_PAIRS.iter().all(|(metric, imperial)| {
+        let (unwanted, wanted) = match units {
+            Units::Metric => (imperial, metric),
+            Units::Imperial => (metric, imperial),
+        };
+
+        match name.strip_suffix(unwanted) {
+            Some(base) => !fields.contains_key(&format!("{}{}", base, wanted)),
+            None => true,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn flattens_hours_in_chosen_units() {
+        let weather = fixture_weather();
+
+        let metric = Table::hourly(&weather, Units::Metric).unwrap();
+        assert_eq!(metric.rows.len(), weather.hours().count());
+        assert_eq!(
+            metric.columns[..4],
+            ["location", "time", "condition", "time_epoch"]
+        );
+        for column in [
+            "temp_c",
+            "wind_kph",
+            "precip_mm",
+            "pressure_mb",
+            "vis_km",
+            "humidity",
+        ] {
+            assert!(
+                metric.columns.iter().any(|name| name == column),
+                "{}",
+                column
+            );
+        }
+        for column in [
+            "temp_f",
+            "wind_mph",
+            "precip_in",
+            "pressure_in",
+            "vis_miles",
+        ] {
+            assert!(
+                !metric.columns.iter().any(|name| name == column),
+                "{}",
+                column
+            );
+        }
+
+        let imperial = Table::hourly(&weather, Units::Imperial).unwrap();
+        for column in ["temp_f", "wind_mph", "precip_in", "pressure_in", "humidity"] {
+            assert!(
+                imperial.columns.iter().any(|name| name == column),
+                "{}",
+                column
+            );
+        }
+        assert!(!imperial.columns.iter().any(|name| name == "temp_c"));
+    }
+
+    #[test]
+    fn writes_selected_columns() {
+        let weather = fixture_weather();
+        let hour = weather.hours().next().unwrap();
+        let columns = vec![String::from("time"), String::from("temp_c")];
+        let table = Table::hourly(&weather, Units::Metric)
+            .unwrap()
+            .select(&columns)
+            .unwrap();
+
+        let mut csv = vec![];
+        table.write_csv(&mut csv, true).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("time,temp_c"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("{},{}", hour.time.format("%Y-%m-%d %H:%M"), hour.temp_c).as_str())
+        );
+
+        let mut ndjson = vec![];
+        table.write_ndjson(&mut ndjson).unwrap();
+        let first: Value =
+            serde_json::from_str(String::from_utf8(ndjson).unwrap().lines().next().unwrap())
+                .unwrap();
+        assert_eq!(first["temp_c"], Value::from(hour.temp_c));
+
+        let unknown = Table::hourly(&weather, Units::Metric)
+            .unwrap()
+            .select(&[String::from("temperature")]);
+        assert!(matches!(unknown, Err(ExportError::UnknownColumn(..))));
+    }
+}
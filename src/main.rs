@@ -1,22 +1,192 @@
-use clap::Parser;
-use dotenv;
+use std::{path::PathBuf, process};
+
+use activity::ActivityProfile;
+use api::{Provider, Query};
+use cache::Cache;
+use chrono::{Duration, Local, NaiveDate};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use config::Config;
+use export::{ExportFormat, ExportOptions, Units};
+use output::Format;
+use recorder::Recorder;
+use summary::Language;
 use ui::start;
 
+mod accuracy;
+mod activity;
+mod advice;
 mod api;
+mod astronomy;
+mod cache;
+mod calendar;
+mod config;
+mod day_parts;
+mod derived;
+mod export;
+mod mock;
+mod mqtt;
+mod notify;
+mod open_meteo;
+mod output;
+mod recorder;
+mod serve;
+mod summary;
 mod ui;
 mod utils;
+mod watch;
 mod weather;
 
+/// Range of days ahead covered by the long-range (future) forecast
+const FUTURE_MIN_DAYS: i64 = 14;
+const FUTURE_MAX_DAYS: i64 = 300;
+/// Days summarised by `history local` when no start date is given
+const RECORDED_DAYS: i64 = 7;
+
 #[derive(Parser, Debug)]
 #[command(version)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Receive weather forecast for this location
-    #[arg(short, long)]
-    location: String,
+    #[arg(short, long, global = true)]
+    location: Option<String>,
 
     /// Optional: specify the length of forecast (in days)
-    #[arg(short, long, default_value_t = 1)]
+    #[arg(short, long, global = true, default_value_t = 1)]
     forecast: u8,
+
+    /// Print current conditions and active weather alerts, then exit without starting the TUI.
+    /// Exit code is 3 (minor) to 6 (extreme) when alerts are active
+    #[arg(short, long, global = true)]
+    once: bool,
+
+    /// Optional: read settings from this file instead of ~/.config/t-weather/config.toml
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Optional: language of the daily summaries
+    #[arg(long, global = true)]
+    lang: Option<Language>,
+
+    /// Print the weather as JSON instead of starting the TUI
+    #[arg(long, global = true, conflicts_with = "template")]
+    json: bool,
+
+    /// Print the weather using a template instead of starting the TUI, e.g. "{location}: {temp_c}C"
+    #[arg(short, long, global = true)]
+    template: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Look up the recorded weather for a past date or date range
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    History {
+        #[command(subcommand)]
+        source: Option<HistorySource>,
+
+        /// First day to look up (YYYY-MM-DD)
+        #[arg(required = true)]
+        date: Option<NaiveDate>,
+
+        /// Optional: last day of the range to look up (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+
+        /// Where to look the weather up. Open-Meteo's archive needs no key and goes back to 1940,
+        /// but trails today by a few days
+        #[arg(long, value_enum, default_value_t)]
+        provider: Provider,
+    },
+    /// Look up the long-range forecast for a date 14 to 300 days ahead
+    Future {
+        /// Day to look up (YYYY-MM-DD)
+        date: NaiveDate,
+    },
+    /// Wave, swell and tide forecast for a coastal location
+    Marine {
+        /// Where to look the forecast up. Open-Meteo needs no key but has no tide times
+        #[arg(long, value_enum, default_value_t)]
+        provider: Provider,
+    },
+    /// What to bring for the time windows set in the config file, e.g. an umbrella or gloves
+    Advice,
+    /// Check the forecast in the background and send desktop notifications when a rule in the
+    /// config file starts to hold. With --once the locations are checked a single time
+    Watch,
+    /// Run an HTTP server with a REST API (/v1/current, /v1/forecast, /v1/search) using this machine's API key
+    Serve {
+        /// Expose current conditions and the near-term forecast as Prometheus gauges at /metrics
+        #[arg(long)]
+        metrics: bool,
+
+        /// Optional: address to listen on instead of the config file's, e.g. 0.0.0.0:9898
+        #[arg(long)]
+        address: Option<String>,
+    },
+    /// Publish current conditions and forecast summaries to an MQTT broker on a schedule,
+    /// for home automation. With --once the locations are published a single time
+    Mqtt,
+    /// Write the hourly forecast as CSV or NDJSON rows, e.g. for spreadsheets
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        #[arg(long, value_enum, default_value_t)]
+        units: Units,
+
+        /// Optional: only these columns, in this order, e.g. time,temp_c,chance_of_rain
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// Leave out the CSV header row
+        #[arg(long)]
+        no_header: bool,
+
+        /// Optional: write to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Write an iCalendar file instead, with an all-day event for each forecast day and
+        /// events for sunrise, sunset and weather alerts
+        #[arg(long, conflicts_with_all = ["format", "units", "columns", "no_header"])]
+        ics: bool,
+    },
+    /// Compare recorded forecasts with what was later observed, per provider and days ahead,
+    /// for --location or every recorded location
+    Accuracy,
+    /// Best upcoming times for the outdoor activities set in the config file
+    Activity {
+        /// Optional: only rank this activity, e.g. running
+        name: Option<String>,
+
+        /// Number of windows to list per activity
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Answer requests like weatherapi.com and Open-Meteo with canned responses, for working
+    /// offline. Point API_URL and OPEN_METEO_URL at the address it prints
+    #[command(hide = true)]
+    MockApi {
+        /// Address to listen on, port 0 picks a free one
+        #[arg(long, default_value = "127.0.0.1:9899")]
+        address: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistorySource {
+    /// Daily highs, lows and rain totals from the weather recorded on this machine
+    Local {
+        /// Optional: first day to summarise (YYYY-MM-DD), a week ago by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+
+        /// Optional: last day to summarise (YYYY-MM-DD), today by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
 }
 
 fn main() {
@@ -24,5 +194,281 @@ fn main() {
 
     let args = Args::parse();
 
-    let _ = start(&args.location);
+    // the mock API stands in for weatherapi.com, so it needs neither the config file nor a key
+    if let Some(Command::MockApi { address }) = &args.command {
+        if let Err(e) = mock::serve(address) {
+            eprintln!("Unable to listen on {}: {}", address, e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    let mut config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(lang) = args.lang {
+        config.language = lang;
+    }
+    let recorder = Recorder::new(&config.recorder);
+
+    // watch, serve and mqtt use the locations in the config file, so --location is optional
+    if let Some(Command::Watch) = args.command {
+        let locations = configured_locations(&config.watch.locations, args.location, "watch");
+        let sinks = match config.watch.build_sinks() {
+            Ok(sinks) => sinks,
+            Err(e) => {
+                eprintln!("Unable to set up notifications: {}", e);
+                process::exit(1);
+            }
+        };
+
+        watch::run(
+            &config.watch,
+            &locations,
+            &config.day_parts,
+            &sinks,
+            config.language,
+            recorder.as_ref(),
+            args.once,
+        );
+        process::exit(0);
+    }
+
+    if let Some(Command::Mqtt) = args.command {
+        let locations = configured_locations(&config.mqtt.locations, args.location, "mqtt");
+        process::exit(mqtt::run(
+            &config.mqtt,
+            &locations,
+            &Cache::new(&config.cache),
+            config.language,
+            recorder.as_ref(),
+            args.once,
+        ));
+    }
+
+    if let Some(Command::Accuracy) = args.command {
+        let recorder = open_recorded(&config);
+        let locations = match args.location {
+            Some(location) => vec![location],
+            None => match accuracy::recorded_locations(&recorder) {
+                Ok(locations) => locations,
+                Err(e) => {
+                    eprintln!(
+                        "Unable to read {}: {}",
+                        recorder.path().to_string_lossy(),
+                        e
+                    );
+                    process::exit(1);
+                }
+            },
+        };
+
+        process::exit(output::print_accuracy(&recorder, &locations, args.json));
+    }
+
+    if let Some(Command::Serve { metrics, address }) = args.command {
+        if let Some(address) = address {
+            config.serve.address = address;
+        }
+
+        let cache = Cache::new(&config.cache);
+        // only the metrics need locations, the REST API is asked for them
+        let latest = match metrics {
+            true => {
+                let locations =
+                    configured_locations(&config.serve.locations, args.location, "serve --metrics");
+                Some(serve::refresh_in_background(
+                    &config.serve,
+                    &locations,
+                    cache.clone(),
+                    recorder,
+                ))
+            }
+            false => None,
+        };
+        if let Err(e) = serve::serve(&config.serve, &cache, latest, config.language) {
+            eprintln!("Unable to listen on {}: {}", config.serve.address, e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    let Some(location) = args.location else {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided:\n  --location <LOCATION>",
+            )
+            .exit();
+    };
+
+    if let Some(Command::History {
+        source: Some(HistorySource::Local { from, to }),
+        ..
+    }) = args.command
+    {
+        let recorder = open_recorded(&config);
+        let to = to.unwrap_or_else(|| Local::now().date_naive());
+        let from = from.unwrap_or(to - Duration::days(RECORDED_DAYS - 1));
+        process::exit(output::print_recorded(
+            &location, &recorder, from, to, args.json,
+        ));
+    }
+
+    let advice = matches!(args.command, Some(Command::Advice));
+    let activity = match &args.command {
+        Some(Command::Activity { name, top }) => Some((name.clone(), *top)),
+        _ => None,
+    };
+    let export = match &args.command {
+        Some(Command::Export {
+            format,
+            units,
+            columns,
+            no_header,
+            output,
+            ics,
+        }) => Some(ExportOptions {
+            format: match ics {
+                true => ExportFormat::Ics,
+                false => *format,
+            },
+            units: *units,
+            columns: columns.clone(),
+            header: !no_header,
+            path: output.clone(),
+        }),
+        _ => None,
+    };
+    let query = match args.command {
+        Some(Command::History {
+            date, to, provider, ..
+        }) => {
+            // clap requires the date unless `local` was given, which has been handled
+            let date = date.unwrap_or_else(|| Local::now().date_naive());
+            Query::History(date, to.unwrap_or(date), provider)
+        }
+        Some(Command::Future { date }) => {
+            let days_ahead = (date - Local::now().date_naive()).num_days();
+            if !(FUTURE_MIN_DAYS..=FUTURE_MAX_DAYS).contains(&days_ahead) {
+                Args::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "future dates must be {} to {} days ahead, use --forecast for nearer dates",
+                            FUTURE_MIN_DAYS, FUTURE_MAX_DAYS
+                        ),
+                    )
+                    .exit();
+            }
+
+            Query::Future(date)
+        }
+        Some(Command::Marine { provider }) => Query::Marine(args.forecast, provider),
+        // windows that have passed today are looked up tomorrow
+        Some(Command::Advice) => Query::Forecast(args.forecast.max(2)),
+        Some(Command::Activity { .. })
+        | Some(Command::Watch)
+        | Some(Command::Serve { .. })
+        | Some(Command::Mqtt)
+        | Some(Command::Accuracy)
+        | Some(Command::Export { .. })
+        | Some(Command::MockApi { .. }) => Query::Forecast(args.forecast),
+        None => Query::Forecast(args.forecast),
+    };
+
+    if advice {
+        process::exit(output::print_advice(&location, &query, &config.advice));
+    }
+
+    if let Some((name, top)) = activity {
+        let profiles: Vec<ActivityProfile> = config
+            .activities
+            .into_iter()
+            .filter(|profile| {
+                name.as_ref()
+                    .is_none_or(|name| profile.name.eq_ignore_ascii_case(name))
+            })
+            .collect();
+        if profiles.is_empty() {
+            Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    "no activity with that name in the config file",
+                )
+                .exit();
+        }
+
+        process::exit(output::print_activities(
+            &location, &query, &profiles, top, args.json,
+        ));
+    }
+
+    if let Some(options) = export {
+        process::exit(output::print_export(
+            &location,
+            &query,
+            &options,
+            config.language,
+        ));
+    }
+
+    let format = match (args.json, args.template) {
+        (true, _) => Some(Format::Json),
+        (false, Some(template)) => Some(Format::Template(template)),
+        (false, None) if args.once => Some(Format::Text),
+        _ => None,
+    };
+
+    if let Some(format) = format {
+        process::exit(output::print_once(
+            &location,
+            &query,
+            &format,
+            config.language,
+            recorder.as_ref(),
+        ));
+    }
+
+    let _ = start(&location, query, config);
+}
+
+/// The recorder's database for reading, exiting when nothing has been recorded.
+/// Reading doesn't need recording to be on, only something recorded earlier
+fn open_recorded(config: &Config) -> Recorder {
+    let Some(recorder) = config.recorder.path().map(Recorder::open) else {
+        eprintln!("No database path, set path under [recorder] in the config file");
+        process::exit(1);
+    };
+    if !recorder.path().exists() {
+        eprintln!("Nothing recorded yet, set enabled = true under [recorder] in the config file");
+        process::exit(1);
+    }
+
+    recorder
+}
+
+/// Locations from the config file, or the one given with --location when there are none
+fn configured_locations(
+    configured: &[String],
+    location: Option<String>,
+    command: &str,
+) -> Vec<String> {
+    match (configured, location) {
+        ([], Some(location)) => vec![location],
+        ([], None) => Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                format!(
+                    "{} needs --location or locations in the config file",
+                    command
+                ),
+            )
+            .exit(),
+        (locations, _) => locations.to_vec(),
+    }
 }
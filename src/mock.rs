@@ -0,0 +1,337 @@
+use std::{collections::HashMap, error::Error};
+
+use chrono::{Duration, NaiveDate};
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::serve::parse_url;
+
+/// Canned responses for each weather endpoint, their days are moved to the dates requested
+const FORECAST: &str = include_str!("../tests/fixtures/forecast.json");
+const HISTORY: &str = include_str!("../tests/fixtures/history.json");
+const FUTURE: &str = include_str!("../tests/fixtures/future.json");
+const MARINE: &str = include_str!("../tests/fixtures/marine.json");
+/// Canned hourly columns for each day requested from Open-Meteo's archive, forecast and marine APIs
+const ARCHIVE: &str = include_str!("../tests/fixtures/open-meteo-archive.json");
+const OPEN_METEO_FORECAST: &str = include_str!("../tests/fixtures/open-meteo-forecast.json");
+const OPEN_METEO_MARINE: &str = include_str!("../tests/fixtures/open-meteo-marine.json");
+/// Locations searched through, by name
+const LOCATIONS: &str = include_str!("../tests/fixtures/search.json");
+
+/// Most days a forecast can be requested for
+const MAX_DAYS: i64 = 14;
+const MAX_OPEN_METEO_DAYS: i64 = 16;
+
+/// Location the mock API finds no match for
+pub const UNKNOWN_LOCATION: &str = "Nowhere";
+/// API key the mock API rejects as invalid
+pub const INVALID_KEY: &str = "invalid";
+/// API key the mock API rejects as over its monthly quota
+pub const EXHAUSTED_KEY: &str = "exhausted";
+
+/// Answers requests like weatherapi.com and Open-Meteo with canned responses until the process
+/// exits, so the app can be run and tested offline
+pub fn serve(address: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(address)?;
+    println!(
+        "Serving a mock weather API at http://{}/v1",
+        server.server_addr()
+    );
+
+    answer(&server);
+
+    Ok(())
+}
+
+/// Mock API on a free port in the background, returns its base URL
+#[cfg(test)]
+pub fn spawn() -> String {
+    let server = Server::http("127.0.0.1:0").expect("a free port is available");
+    let url = format!("http://{}/v1", server.server_addr());
+    std::thread::spawn(move || answer(&server));
+
+    url
+}
+
+fn answer(server: &Server) {
+    for request in server.incoming_requests() {
+        let (path, params) = parse_url(request.url());
+        let (status, body) = respond(&path, &params);
+
+        let content_type =
+            Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            eprintln!("Unable to respond: {}", e);
+        }
+    }
+}
+
+/// Status and body weatherapi.com answers the request with. The key is checked before the location
+fn respond(path: &str, params: &HashMap<String, String>) -> (u16, String) {
+    let endpoint = path.strip_prefix("/v1/").unwrap_or(path);
+    if let Some(response) = respond_open_meteo(endpoint, params) {
+        return response;
+    }
+    if !matches!(
+        endpoint,
+        "forecast.json" | "history.json" | "future.json" | "marine.json" | "search.json"
+    ) {
+        return error(400, 1005, "API request url is invalid.");
+    }
+
+    match params.get("key").map(String::as_str) {
+        None | Some("") => return error(401, 1002, "API key is invalid or not provided."),
+        Some(INVALID_KEY) => return error(401, 2006, "API key provided is invalid"),
+        Some(EXHAUSTED_KEY) => {
+            return error(403, 2007, "API key has exceeded calls per month quota.")
+        }
+        Some(_) => {}
+    }
+
+    let text = match params.get("q").map(|q| q.trim()) {
+        None | Some("") => return error(400, 1003, "Parameter q is missing."),
+        Some(text) => text.to_lowercase(),
+    };
+
+    match endpoint {
+        // searches without a match are answered with an empty list rather than an error
+        "search.json" => {
+            let locations: Vec<Value> = serde_json::from_str(LOCATIONS).unwrap_or_default();
+            let matching: Vec<&Value> = locations
+                .iter()
+                .filter(|location| {
+                    location["name"]
+                        .as_str()
+                        .is_some_and(|name| name.to_lowercase().contains(&text))
+                })
+                .collect();
+
+            (200, json!(matching).to_string())
+        }
+        _ if text == UNKNOWN_LOCATION.to_lowercase() => {
+            error(400, 1006, "No matching location found.")
+        }
+        // like weatherapi.com, a missing or invalid number of days means one day
+        "forecast.json" | "marine.json" => {
+            let fixture = match endpoint {
+                "forecast.json" => FORECAST,
+                _ => MARINE,
+            };
+            let days = params
+                .get("days")
+                .and_then(|days| days.parse().ok())
+                .unwrap_or(1);
+
+            (200, with_days(fixture, None, days.clamp(1, MAX_DAYS)))
+        }
+        "history.json" => {
+            let from = date_param(params, "dt");
+            let to = params
+                .get("end_dt")
+                .map_or(from, |_| date_param(params, "end_dt"));
+            match (from, to) {
+                (Some(from), Some(to)) if from <= to => {
+                    let days = (to - from).num_days() + 1;
+                    (200, with_days(HISTORY, Some(from), days))
+                }
+                _ => error(400, 1005, "API request url is invalid."),
+            }
+        }
+        _ => match date_param(params, "dt") {
+            Some(date) => (200, with_days(FUTURE, Some(date), 1)),
+            None => error(400, 1005, "API request url is invalid."),
+        },
+    }
+}
+
+/// Status and body Open-Meteo answers the request with, None for weatherapi.com's endpoints
+fn respond_open_meteo(endpoint: &str, params: &HashMap<String, String>) -> Option<(u16, String)> {
+    match endpoint {
+        // like Open-Meteo, `results` is left out when nothing matches
+        "search" => {
+            let text = params
+                .get("name")
+                .map_or(String::new(), |name| name.trim().to_lowercase());
+            let locations: Vec<Value> = serde_json::from_str(LOCATIONS).unwrap_or_default();
+            let results: Vec<Value> = locations
+                .iter()
+                .filter(|location| {
+                    location["name"]
+                        .as_str()
+                        .is_some_and(|name| !text.is_empty() && name.to_lowercase().contains(&text))
+                })
+                .map(|location| {
+                    json!({
+                        "name": location["name"],
+                        "latitude": location["lat"],
+                        "longitude": location["lon"],
+                        "admin1": location["region"],
+                        "country": location["country"],
+                    })
+                })
+                .collect();
+
+            let body = match results.is_empty() {
+                true => json!({ "generationtime_ms": 0.1 }),
+                false => json!({ "results": results }),
+            };
+            Some((200, body.to_string()))
+        }
+        "archive" => match (
+            date_param(params, "start_date"),
+            date_param(params, "end_date"),
+        ) {
+            (Some(from), Some(to)) if from <= to => {
+                let days = (to - from).num_days() + 1;
+                Some((200, with_hours(ARCHIVE, Some(from), days)))
+            }
+            _ => Some(open_meteo_error(
+                "Parameter 'start_date' and 'end_date' must be dates with the start first",
+            )),
+        },
+        // like Open-Meteo, a missing number of days means a week
+        "forecast" | "marine" => {
+            let fixture = match endpoint {
+                "forecast" => OPEN_METEO_FORECAST,
+                _ => OPEN_METEO_MARINE,
+            };
+            match params.get("forecast_days").map(|days| days.parse()) {
+                None => Some((200, with_hours(fixture, None, 7))),
+                Some(Ok(days @ 1..=MAX_OPEN_METEO_DAYS)) => {
+                    Some((200, with_hours(fixture, None, days)))
+                }
+                Some(_) => Some(open_meteo_error(
+                    "Parameter 'forecast_days' must be between 0 and 16",
+                )),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Date parameter in the `YYYY-MM-DD` format the API uses
+fn date_param(params: &HashMap<String, String>, name: &str) -> Option<NaiveDate> {
+    params.get(name).and_then(|date| date.parse().ok())
+}
+
+/// The fixture with `days` forecast days from `start`, or from its own first day. Its days are
+/// repeated when more are asked for than it has
+fn with_days(fixture: &str, start: Option<NaiveDate>, days: i64) -> String {
+    let mut weather: Value = serde_json::from_str(fixture).expect("fixture is valid JSON");
+    let Some(forecastday) = weather
+        .pointer_mut("/forecast/forecastday")
+        .and_then(Value::as_array_mut)
+    else {
+        return weather.to_string();
+    };
+
+    let start = start
+        .or_else(|| forecastday.first().and_then(date_of))
+        .unwrap_or_default();
+    let templates = std::mem::take(forecastday);
+    if !templates.is_empty() {
+        *forecastday = (0..days)
+            .map(|i| {
+                move_day(
+                    &templates[i as usize % templates.len()],
+                    start + Duration::days(i),
+                )
+            })
+            .collect();
+    }
+
+    weather.to_string()
+}
+
+/// The Open-Meteo fixture with its hourly columns repeated for `days` days from `start`, or
+/// from its own first day
+fn with_hours(fixture: &str, start: Option<NaiveDate>, days: i64) -> String {
+    let mut response: Value = serde_json::from_str(fixture).expect("fixture is valid JSON");
+    let start = start
+        .or_else(|| {
+            let first = response.pointer("/hourly/time/0")?.as_str()?;
+            first.get(..10)?.parse().ok()
+        })
+        .unwrap_or_default();
+    if let Some(hourly) = response.get_mut("hourly").and_then(Value::as_object_mut) {
+        for (name, column) in hourly.iter_mut() {
+            let template = column.as_array().cloned().unwrap_or_default();
+            let values = (0..days)
+                .flat_map(|i| {
+                    let date = (start + Duration::days(i)).format("%Y-%m-%d").to_string();
+                    template.iter().map(move |value| {
+                        let mut value = value.clone();
+                        if name == "time" {
+                            move_time(&mut value, &date);
+                        }
+                        value
+                    })
+                })
+                .collect();
+            *column = Value::Array(values);
+        }
+    }
+
+    response.to_string()
+}
+
+fn date_of(day: &Value) -> Option<NaiveDate> {
+    day["date"].as_str().and_then(|date| date.parse().ok())
+}
+
+/// Copy of the forecast day moved to another date, along with its hours and tides
+fn move_day(day: &Value, date: NaiveDate) -> Value {
+    let mut day = day.clone();
+    let seconds = date_of(&day).map_or(0, |from| (date - from).num_seconds());
+    let date = date.format("%Y-%m-%d").to_string();
+
+    day["date"] = json!(date);
+    move_epoch(&mut day["date_epoch"], seconds);
+    if let Some(hours) = day.get_mut("hour").and_then(Value::as_array_mut) {
+        for hour in hours {
+            move_time(&mut hour["time"], &date);
+            move_epoch(&mut hour["time_epoch"], seconds);
+        }
+    }
+    if let Some(tides) = day.pointer_mut("/day/tides").and_then(Value::as_array_mut) {
+        for tide in tides
+            .iter_mut()
+            .filter_map(|tides| tides.get_mut("tide").and_then(Value::as_array_mut))
+            .flatten()
+        {
+            move_time(&mut tide["tide_time"], &date);
+        }
+    }
+
+    day
+}
+
+fn move_epoch(epoch: &mut Value, seconds: i64) {
+    if let Some(value) = epoch.as_i64() {
+        *epoch = json!(value + seconds);
+    }
+}
+
+/// Replaces the date of a "YYYY-MM-DD HH:MM" time, or Open-Meteo's "YYYY-MM-DDTHH:MM"
+fn move_time(time: &mut Value, date: &str) {
+    if let Some(clock) = time.as_str().and_then(|time| time.get(10..)) {
+        *time = json!(format!("{}{}", date, clock));
+    }
+}
+
+/// e.g. {"error":{"code":1006,"message":"No matching location found."}}
+fn error(status: u16, code: i64, message: &str) -> (u16, String) {
+    let body = json!({ "error": { "code": code, "message": message } });
+
+    (status, body.to_string())
+}
+
+/// e.g. {"error":true,"reason":"Parameter 'start_date' is out of allowed range"}
+fn open_meteo_error(reason: &str) -> (u16, String) {
+    let body = json!({ "error": true, "reason": reason });
+
+    (400, body.to_string())
+}
@@ -0,0 +1,558 @@
+use std::{thread, time::Duration};
+
+use rumqttc::{Client, Event, MqttOptions, Outgoing, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    api::Query,
+    cache::Cache,
+    recorder::{record, Recorder},
+    summary::{summarize_day, Language},
+    utils::slug,
+    weather::{Forecastday, Weather},
+};
+
+/// Days of forecast summaries published for each location
+const MQTT_FORECAST_DAYS: u8 = 3;
+/// Fewest publishes that can wait while the connection to the broker is being made
+const MQTT_QUEUE: usize = 256;
+/// Messages published for each location besides the discovery config: current and forecast
+const STATE_MESSAGES: usize = 2;
+/// Pause before reconnecting after the broker drops or refuses the connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Broker, topics and schedule for the `mqtt` subcommand
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Published instead of --location when set
+    pub locations: Vec<String>,
+    pub interval_minutes: u64,
+    /// Topics are `{topic_prefix}/{location}/current` and `{topic_prefix}/{location}/forecast`
+    pub topic_prefix: String,
+    /// Also publish Home Assistant MQTT discovery config, so the sensors appear automatically
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+/// Message sent to the broker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+/// Day of the forecast as published on the forecast topic
+#[derive(Debug, Serialize)]
+struct DaySummary<'a> {
+    date: String,
+    maxtemp_c: f64,
+    mintemp_c: f64,
+    daily_chance_of_rain: i64,
+    totalprecip_mm: f64,
+    condition: &'a str,
+    summary: String,
+}
+
+/// Home Assistant sensor read from one of the published topics
+struct Sensor {
+    key: &'static str,
+    name: &'static str,
+    /// `current` or `forecast`
+    topic: &'static str,
+    value_template: &'static str,
+    unit: Option<&'static str>,
+    device_class: Option<&'static str>,
+}
+
+const SENSORS: [Sensor; 12] = [
+    Sensor {
+        key: "temperature",
+        name: "Temperature",
+        topic: "current",
+        value_template: "{{ value_json.temp_c }}",
+        unit: Some("°C"),
+        device_class: Some("temperature"),
+    },
+    Sensor {
+        key: "feels_like",
+        name: "Feels like",
+        topic: "current",
+        value_template: "{{ value_json.feelslike_c }}",
+        unit: Some("°C"),
+        device_class: Some("temperature"),
+    },
+    Sensor {
+        key: "humidity",
+        name: "Humidity",
+        topic: "current",
+        value_template: "{{ value_json.humidity }}",
+        unit: Some("%"),
+        device_class: Some("humidity"),
+    },
+    Sensor {
+        key: "pressure",
+        name: "Pressure",
+        topic: "current",
+        value_template: "{{ value_json.pressure_mb }}",
+        unit: Some("hPa"),
+        device_class: Some("atmospheric_pressure"),
+    },
+    Sensor {
+        key: "wind_speed",
+        name: "Wind speed",
+        topic: "current",
+        value_template: "{{ value_json.wind_kph }}",
+        unit: Some("km/h"),
+        device_class: Some("wind_speed"),
+    },
+    Sensor {
+        key: "wind_gust",
+        name: "Wind gust",
+        topic: "current",
+        value_template: "{{ value_json.gust_kph }}",
+        unit: Some("km/h"),
+        device_class: Some("wind_speed"),
+    },
+    Sensor {
+        key: "precipitation",
+        name: "Precipitation",
+        topic: "current",
+        value_template: "{{ value_json.precip_mm }}",
+        unit: Some("mm"),
+        device_class: Some("precipitation"),
+    },
+    Sensor {
+        key: "uv",
+        name: "UV index",
+        topic: "current",
+        value_template: "{{ value_json.uv }}",
+        unit: None,
+        device_class: None,
+    },
+    Sensor {
+        key: "cloud_cover",
+        name: "Cloud cover",
+        topic: "current",
+        value_template: "{{ value_json.cloud }}",
+        unit: Some("%"),
+        device_class: None,
+    },
+    Sensor {
+        key: "condition",
+        name: "Condition",
+        topic: "current",
+        value_template: "{{ value_json.condition.text }}",
+        unit: None,
+        device_class: None,
+    },
+    Sensor {
+        key: "chance_of_rain",
+        name: "Chance of rain today",
+        topic: "forecast",
+        value_template: "{{ value_json[0].daily_chance_of_rain }}",
+        unit: Some("%"),
+        device_class: None,
+    },
+    Sensor {
+        key: "summary",
+        name: "Today",
+        topic: "forecast",
+        value_template: "{{ value_json[0].summary }}",
+        unit: None,
+        device_class: None,
+    },
+];
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            host: String::from("localhost"),
+            port: 1883,
+            client_id: String::from("t-weather"),
+            username: None,
+            password: None,
+            locations: vec![],
+            interval_minutes: 15,
+            topic_prefix: String::from("t-weather"),
+            discovery: false,
+            discovery_prefix: String::from("homeassistant"),
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        // publishing without a pause would call the API and the broker as fast as they answer
+        if self.interval_minutes < 1 {
+            return Err(String::from("mqtt interval_minutes must be at least 1"));
+        }
+
+        Ok(())
+    }
+
+    /// Messages published for each location every round
+    fn messages_per_location(&self) -> usize {
+        match self.discovery {
+            true => SENSORS.len() + STATE_MESSAGES,
+            false => STATE_MESSAGES,
+        }
+    }
+
+    /// Requests the client can queue: a whole round of messages and the disconnect after it,
+    /// so publishing never waits on the connection while it is being made or has given up
+    fn queue_capacity(&self, locations: usize) -> usize {
+        MQTT_QUEUE.max(locations * self.messages_per_location() + 1)
+    }
+
+    fn topic(&self, location: &str, name: &str) -> String {
+        format!("{}/{}/{}", self.topic_prefix, slug(location), name)
+    }
+}
+
+/// Current conditions and the daily forecast summaries for a location, retained so new subscribers get them straight away
+pub fn state_messages(
+    config: &MqttConfig,
+    location: &str,
+    weather: &Weather,
+    language: Language,
+) -> Vec<Message> {
+    let forecast: Vec<DaySummary> = weather
+        .forecast
+        .forecastday
+        .iter()
+        .map(|day| day_summary(day, language))
+        .collect();
+
+    vec![
+        Message {
+            topic: config.topic(location, "current"),
+            payload: serde_json::to_string(&weather.current).unwrap_or_default(),
+            retain: true,
+        },
+        Message {
+            topic: config.topic(location, "forecast"),
+            payload: serde_json::to_string(&forecast).unwrap_or_default(),
+            retain: true,
+        },
+    ]
+}
+
+/// Home Assistant discovery config for each sensor, grouped into one device per location
+pub fn discovery_messages(config: &MqttConfig, location: &str) -> Vec<Message> {
+    let device_id = format!("t_weather_{}", slug(location));
+
+    SENSORS
+        .iter()
+        .map(|sensor| {
+            let unique_id = format!("{}_{}", device_id, sensor.key);
+            let mut payload = json!({
+                "name": sensor.name,
+                "unique_id": unique_id,
+                "object_id": unique_id,
+                "state_topic": config.topic(location, sensor.topic),
+                "value_template": sensor.value_template,
+                "device": {
+                    "identifiers": [device_id],
+                    "name": format!("Weather {}", location),
+                    "manufacturer": "t-weather",
+                },
+            });
+            if let Some(unit) = sensor.unit {
+                payload["unit_of_measurement"] = json!(unit);
+                payload["state_class"] = json!("measurement");
+            }
+            if let Some(device_class) = sensor.device_class {
+                payload["device_class"] = json!(device_class);
+            }
+
+            Message {
+                topic: format!("{}/sensor/{}/config", config.discovery_prefix, unique_id),
+                payload: payload.to_string(),
+                retain: true,
+            }
+        })
+        .collect()
+}
+
+/// Publishes each location's weather every `interval_minutes` until the process is stopped,
+/// or a single time with `once`. Returns the exit code
+pub fn run(
+    config: &MqttConfig,
+    locations: &[String],
+    cache: &Cache,
+    language: Language,
+    recorder: Option<&Recorder>,
+    once: bool,
+) -> i32 {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(60));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let (client, mut connection) = Client::new(options, config.queue_capacity(locations.len()));
+    let events = thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => return true,
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("MQTT connection error: {}", e);
+                    // a single run gives up rather than waiting for the broker
+                    if once {
+                        return false;
+                    }
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+
+        true
+    });
+
+    let query = Query::Forecast(MQTT_FORECAST_DAYS);
+    loop {
+        for location in locations {
+            let weather = match cache.get_weather(location, &query) {
+                Ok(weather) => weather,
+                Err(e) => {
+                    // the retained messages from the last fetch stay on the broker
+                    eprintln!("Unable to fetch weather for {}: {}", location, e);
+                    continue;
+                }
+            };
+            record(recorder, location, &weather);
+
+            // discovery config is republished each time, in case the broker lost its retained messages
+            let mut messages = match config.discovery {
+                true => discovery_messages(config, location),
+                false => vec![],
+            };
+            messages.extend(state_messages(config, location, &weather, language));
+
+            for message in messages {
+                if let Err(e) = client.publish(
+                    message.topic,
+                    QoS::AtLeastOnce,
+                    message.retain,
+                    message.payload,
+                ) {
+                    eprintln!("Unable to publish weather for {}: {}", location, e);
+                }
+            }
+        }
+
+        if once {
+            break;
+        }
+        thread::sleep(Duration::from_secs(config.interval_minutes * 60));
+    }
+
+    // queued publishes are sent before the disconnect
+    let _ = client.disconnect();
+    match events.join() {
+        Ok(true) => 0,
+        _ => 1,
+    }
+}
+
+fn day_summary(day: &Forecastday, language: Language) -> DaySummary<'_> {
+    DaySummary {
+        date: day.date.format("%Y-%m-%d").to_string(),
+        maxtemp_c: day.day.maxtemp_c,
+        mintemp_c: day.day.mintemp_c,
+        daily_chance_of_rain: day.day.daily_chance_of_rain,
+        totalprecip_mm: day.day.totalprecip_mm,
+        condition: &day.day.condition.text,
+        summary: summarize_day(day, language),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn publishes_current_and_forecast_topics() {
+        let weather = fixture_weather();
+        let config = MqttConfig::default();
+
+        let messages = state_messages(&config, "New York, US", &weather, Language::En);
+        assert_eq!(messages[0].topic, "t-weather/new_york_us/current");
+        assert_eq!(messages[1].topic, "t-weather/new_york_us/forecast");
+        assert!(messages.iter().all(|message| message.retain));
+
+        let current: serde_json::Value = serde_json::from_str(&messages[0].payload).unwrap();
+        assert_eq!(current["temp_c"], json!(weather.current.unwrap().temp_c));
+        let days: serde_json::Value = serde_json::from_str(&messages[1].payload).unwrap();
+        assert_eq!(
+            days.as_array().unwrap().len(),
+            weather.forecast.forecastday.len()
+        );
+        assert!(!days[0]["summary"].as_str().unwrap().is_empty());
+    }
+
+    #[test]
+    fn describes_sensors_for_home_assistant() {
+        let config = MqttConfig::default();
+
+        let messages = discovery_messages(&config, "London");
+        assert_eq!(messages.len(), SENSORS.len());
+        assert_eq!(
+            messages[0].topic,
+            "homeassistant/sensor/t_weather_london_temperature/config"
+        );
+
+        let payload: serde_json::Value = serde_json::from_str(&messages[0].payload).unwrap();
+        assert_eq!(payload["state_topic"], json!("t-weather/london/current"));
+        assert_eq!(payload["device_class"], json!("temperature"));
+        assert_eq!(
+            payload["device"]["identifiers"],
+            json!(["t_weather_london"])
+        );
+    }
+
+    #[test]
+    fn keeps_non_ascii_locations_apart() {
+        let config = MqttConfig::default();
+        let weather = fixture_weather();
+
+        let beijing = state_messages(&config, "北京", &weather, Language::En);
+        let tokyo = state_messages(&config, "東京", &weather, Language::En);
+        assert_eq!(beijing[0].topic, "t-weather/e58c97e4baac/current");
+        assert_ne!(beijing[0].topic, tokyo[0].topic);
+
+        let beijing = discovery_messages(&config, "北京");
+        let tokyo = discovery_messages(&config, "東京");
+        let device = |messages: &[Message]| -> serde_json::Value {
+            serde_json::from_str::<serde_json::Value>(&messages[0].payload).unwrap()["device"]
+                ["identifiers"]
+                .clone()
+        };
+        assert_ne!(device(&beijing), device(&tokyo));
+        assert_ne!(beijing[0].topic, tokyo[0].topic);
+    }
+
+    #[test]
+    fn validates_interval_and_counts_messages() {
+        let config = MqttConfig {
+            discovery: true,
+            ..MqttConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        let zero = MqttConfig {
+            interval_minutes: 0,
+            ..config.clone()
+        };
+        assert!(zero.validate().is_err());
+
+        let messages = discovery_messages(&config, "London").len()
+            + state_messages(&config, "London", &fixture_weather(), Language::En).len();
+        assert_eq!(config.messages_per_location(), messages);
+        assert_eq!(config.queue_capacity(1), MQTT_QUEUE);
+        assert_eq!(config.queue_capacity(100), 100 * messages + 1);
+    }
+
+    /// Reads one MQTT packet, returning its type and body
+    fn read_packet(stream: &mut impl Read) -> (u8, Vec<u8>) {
+        let mut header = [0; 1];
+        stream.read_exact(&mut header).unwrap();
+
+        let (mut length, mut shift) = (0, 0);
+        loop {
+            let mut byte = [0; 1];
+            stream.read_exact(&mut byte).unwrap();
+            length += ((byte[0] & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        (header[0], body)
+    }
+
+    #[test]
+    fn publishes_to_broker() {
+        // stand-in broker that acknowledges everything and records the published topics
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let broker = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut published = vec![];
+            // acks may fail once the client has sent its DISCONNECT and closed the socket,
+            // the packets it sent before that can still be read
+            loop {
+                let (header, body) = read_packet(&mut stream);
+                match header >> 4 {
+                    // CONNECT
+                    1 => {
+                        let _ = stream.write_all(&[0x20, 0x02, 0x00, 0x00]);
+                    }
+                    // PUBLISH at QoS 1, acknowledged with its packet id
+                    3 => {
+                        let topic_length = u16::from_be_bytes([body[0], body[1]]) as usize;
+                        let topic = String::from_utf8(body[2..2 + topic_length].to_vec()).unwrap();
+                        let id = &body[2 + topic_length..4 + topic_length];
+                        let _ = stream.write_all(&[0x40, 0x02, id[0], id[1]]);
+                        published.push((topic, header & 0x01 == 1));
+                    }
+                    // DISCONNECT
+                    14 => return published,
+                    _ => {}
+                }
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!("t-weather-mqtt-{}", std::process::id()));
+        let cache = Cache::new(&crate::cache::CacheConfig {
+            ttl_minutes: 15,
+            dir: Some(dir.clone()),
+        });
+        // seed the cache, so the run doesn't need the API
+        cache.seed(
+            "London",
+            &Query::Forecast(MQTT_FORECAST_DAYS),
+            &fixture_weather(),
+        );
+
+        let config = MqttConfig {
+            port,
+            discovery: true,
+            ..MqttConfig::default()
+        };
+        assert_eq!(
+            run(
+                &config,
+                &[String::from("London")],
+                &cache,
+                Language::En,
+                None,
+                true
+            ),
+            0
+        );
+
+        let published = broker.join().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(published.len(), SENSORS.len() + 2);
+        assert!(published.iter().all(|(_, retain)| *retain));
+        assert_eq!(published[SENSORS.len()].0, "t-weather/london/current");
+    }
+}
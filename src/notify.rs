@@ -0,0 +1,576 @@
+use std::{collections::HashMap, fmt, thread, time::Duration};
+
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+use zbus::{blocking::Connection, zvariant::Value};
+
+const APP_NAME: &str = "t-weather";
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+/// Urgency hint values defined by the desktop notifications spec
+const URGENCY_NORMAL: u8 = 1;
+const URGENCY_CRITICAL: u8 = 2;
+/// Let the notification server decide how long notifications stay on screen
+const DEFAULT_EXPIRE_TIMEOUT: i32 = -1;
+
+const DEFAULT_SMTP_PORT: u16 = 587;
+/// Delivery attempts per notification, and the wait before the first retry which doubles each time
+const DEFAULT_ATTEMPTS: u32 = 3;
+const DEFAULT_BACKOFF_SECS: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+    /// Shown as critical, e.g. for severe weather alerts
+    pub urgent: bool,
+}
+
+#[derive(Debug)]
+pub enum NotifyError {
+    DBus(zbus::Error),
+    Request(reqwest::Error),
+    /// Webhook responded with a non-success status code
+    Status(u16),
+    Address(lettre::address::AddressError),
+    Message(lettre::error::Error),
+    Smtp(lettre::transport::smtp::Error),
+}
+
+/// Named notification sink from the config file, rules refer to sinks by name
+#[derive(Debug, Clone, Deserialize)]
+pub struct SinkConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    #[serde(default = "default_backoff_secs")]
+    pub backoff_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SinkKind {
+    /// Desktop notification over D-Bus
+    Desktop,
+    /// JSON payload with the summary, body and urgency
+    Webhook { url: String },
+    /// Slack incoming webhook
+    Slack { url: String },
+    /// Discord webhook
+    Discord { url: String },
+    /// `m.room.message` event content, e.g. for a Matrix webhook bridge
+    Matrix {
+        url: String,
+        access_token: Option<String>,
+    },
+    Email {
+        server: String,
+        #[serde(default = "default_smtp_port")]
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+/// Shape of the JSON body posted by a `WebhookNotifier`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    Json,
+    Slack,
+    Discord,
+    Matrix,
+}
+
+/// Somewhere notifications can be delivered to
+pub trait Notifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotifyError>;
+}
+
+/// Sends desktop notifications to `org.freedesktop.Notifications` over D-Bus
+pub struct DBusNotifier {
+    connection: Connection,
+}
+
+/// Posts notifications to an HTTP endpoint
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+    format: MessageFormat,
+    access_token: Option<String>,
+}
+
+/// Emails notifications over SMTP with STARTTLS
+pub struct EmailNotifier {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+/// Retries failed deliveries, waiting `backoff` before the first retry and doubling it each time
+pub struct RetryNotifier {
+    inner: Box<dyn Notifier>,
+    attempts: u32,
+    backoff: Duration,
+}
+
+fn default_attempts() -> u32 {
+    DEFAULT_ATTEMPTS
+}
+
+fn default_backoff_secs() -> u64 {
+    DEFAULT_BACKOFF_SECS
+}
+
+fn default_smtp_port() -> u16 {
+    DEFAULT_SMTP_PORT
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::DBus(e) => write!(f, "D-Bus error: {}", e),
+            NotifyError::Request(e) => write!(f, "Request failed: {}", e),
+            NotifyError::Status(code) => write!(f, "Webhook responded with status {}", code),
+            NotifyError::Address(e) => write!(f, "Invalid email address: {}", e),
+            NotifyError::Message(e) => write!(f, "Unable to build email: {}", e),
+            NotifyError::Smtp(e) => write!(f, "SMTP error: {}", e),
+        }
+    }
+}
+
+impl From<zbus::Error> for NotifyError {
+    fn from(e: zbus::Error) -> Self {
+        NotifyError::DBus(e)
+    }
+}
+
+impl From<reqwest::Error> for NotifyError {
+    fn from(e: reqwest::Error) -> Self {
+        NotifyError::Request(e)
+    }
+}
+
+impl From<lettre::address::AddressError> for NotifyError {
+    fn from(e: lettre::address::AddressError) -> Self {
+        NotifyError::Address(e)
+    }
+}
+
+impl From<lettre::error::Error> for NotifyError {
+    fn from(e: lettre::error::Error) -> Self {
+        NotifyError::Message(e)
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for NotifyError {
+    fn from(e: lettre::transport::smtp::Error) -> Self {
+        NotifyError::Smtp(e)
+    }
+}
+
+impl SinkConfig {
+    /// Sink named `desktop` that is available without being configured
+    pub fn desktop() -> SinkConfig {
+        SinkConfig {
+            name: String::from("desktop"),
+            kind: SinkKind::Desktop,
+            attempts: DEFAULT_ATTEMPTS,
+            backoff_secs: DEFAULT_BACKOFF_SECS,
+        }
+    }
+
+    /// Connects to the sink, retrying failed deliveries as configured
+    pub fn build(&self) -> Result<Box<dyn Notifier>, NotifyError> {
+        let inner: Box<dyn Notifier> = match &self.kind {
+            SinkKind::Desktop => Box::new(DBusNotifier::session()?),
+            SinkKind::Webhook { url } => Box::new(WebhookNotifier::new(url, MessageFormat::Json)),
+            SinkKind::Slack { url } => Box::new(WebhookNotifier::new(url, MessageFormat::Slack)),
+            SinkKind::Discord { url } => {
+                Box::new(WebhookNotifier::new(url, MessageFormat::Discord))
+            }
+            SinkKind::Matrix { url, access_token } => Box::new(WebhookNotifier {
+                access_token: access_token.clone(),
+                ..WebhookNotifier::new(url, MessageFormat::Matrix)
+            }),
+            SinkKind::Email {
+                server,
+                port,
+                username,
+                password,
+                from,
+                to,
+            } => {
+                let mut transport = SmtpTransport::starttls_relay(server)?.port(*port);
+                if let (Some(username), Some(password)) = (username, password) {
+                    transport =
+                        transport.credentials(Credentials::new(username.clone(), password.clone()));
+                }
+
+                Box::new(EmailNotifier {
+                    transport: transport.build(),
+                    from: from.parse()?,
+                    to: to
+                        .iter()
+                        .map(|address| address.parse())
+                        .collect::<Result<_, _>>()?,
+                })
+            }
+        };
+
+        Ok(Box::new(RetryNotifier::new(
+            inner,
+            self.attempts,
+            Duration::from_secs(self.backoff_secs),
+        )))
+    }
+}
+
+impl DBusNotifier {
+    pub fn new(connection: Connection) -> DBusNotifier {
+        DBusNotifier { connection }
+    }
+
+    /// Connects to the user's session bus
+    pub fn session() -> Result<DBusNotifier, NotifyError> {
+        Ok(DBusNotifier::new(Connection::session()?))
+    }
+}
+
+impl Notifier for DBusNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let urgency = match notification.urgent {
+            true => URGENCY_CRITICAL,
+            false => URGENCY_NORMAL,
+        };
+        let hints = HashMap::from([("urgency", Value::U8(urgency))]);
+        let actions: Vec<&str> = vec![];
+
+        self.connection.call_method(
+            Some(NOTIFICATIONS_NAME),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS_NAME),
+            "Notify",
+            &(
+                APP_NAME,
+                0u32,
+                "",
+                notification.summary.as_str(),
+                notification.body.as_str(),
+                actions,
+                hints,
+                DEFAULT_EXPIRE_TIMEOUT,
+            ),
+        )?;
+
+        Ok(())
+    }
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str, format: MessageFormat) -> WebhookNotifier {
+        WebhookNotifier {
+            client: Client::new(),
+            url: url.to_string(),
+            format,
+            access_token: None,
+        }
+    }
+}
+
+impl MessageFormat {
+    /// JSON body for the notification in this format
+    pub fn payload(&self, notification: &Notification) -> Json {
+        match self {
+            MessageFormat::Json => json!({
+                "summary": notification.summary,
+                "body": notification.body,
+                "urgent": notification.urgent,
+            }),
+            MessageFormat::Slack => json!({
+                "text": format!("*{}*\n{}", notification.summary, notification.body),
+            }),
+            MessageFormat::Discord => json!({
+                "content": format!("**{}**\n{}", notification.summary, notification.body),
+            }),
+            MessageFormat::Matrix => json!({
+                "msgtype": "m.text",
+                "body": format!("{}\n{}", notification.summary, notification.body),
+                "format": "org.matrix.custom.html",
+                "formatted_body": format!(
+                    "<b>{}</b><br>{}",
+                    escape_html(&notification.summary),
+                    escape_html(&notification.body)
+                ),
+            }),
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .json(&self.format.payload(notification));
+        if let Some(token) = &self.access_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send()?;
+        if !response.status().is_success() {
+            return Err(NotifyError::Status(response.status().as_u16()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(&notification.summary);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+
+        self.transport
+            .send(&message.body(notification.body.clone())?)?;
+
+        Ok(())
+    }
+}
+
+impl RetryNotifier {
+    pub fn new(inner: Box<dyn Notifier>, attempts: u32, backoff: Duration) -> RetryNotifier {
+        RetryNotifier {
+            inner,
+            attempts: attempts.max(1),
+            backoff,
+        }
+    }
+}
+
+impl Notifier for RetryNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+
+        loop {
+            match self.inner.notify(notification) {
+                Err(e) if attempt < self.attempts => {
+                    eprintln!(
+                        "Notification attempt {} of {} failed, retrying: {}",
+                        attempt, self.attempts, e
+                    );
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        os::unix::net::UnixStream,
+        sync::mpsc::{self, Sender},
+    };
+
+    use zbus::{blocking::connection::Builder, interface, Guid};
+
+    use super::*;
+
+    /// Stand-in for the desktop's notification server, records what it is sent
+    struct NotificationServer {
+        received: Sender<(String, String, String, u8)>,
+    }
+
+    #[interface(name = "org.freedesktop.Notifications")]
+    impl NotificationServer {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            hints: HashMap<String, Value<'_>>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = match hints.get("urgency") {
+                Some(Value::U8(urgency)) => *urgency,
+                _ => 0,
+            };
+            self.received
+                .send((app_name, summary, body, urgency))
+                .unwrap();
+
+            1
+        }
+    }
+
+    #[test]
+    fn sends_notifications_over_dbus() {
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        // the peer-to-peer handshake blocks until both ends are connected
+        let server = thread::spawn(move || {
+            Builder::unix_stream(server_stream)
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(NOTIFICATIONS_PATH, NotificationServer { received: sender })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::unix_stream(client_stream).p2p().build().unwrap();
+        let _server = server.join().unwrap();
+
+        let notifier = DBusNotifier::new(client);
+        notifier
+            .notify(&Notification {
+                summary: String::from("London: Yellow warning for rain"),
+                body: String::from("Heavy rain from 14:00"),
+                urgent: true,
+            })
+            .unwrap();
+
+        assert_eq!(
+            receiver.recv().unwrap(),
+            (
+                String::from(APP_NAME),
+                String::from("London: Yellow warning for rain"),
+                String::from("Heavy rain from 14:00"),
+                URGENCY_CRITICAL
+            )
+        );
+    }
+
+    fn notification() -> Notification {
+        Notification {
+            summary: String::from("London: Chance of rain above 60%"),
+            body: String::from("Chance of rain 85% at <Thu 14:00>"),
+            urgent: false,
+        }
+    }
+
+    #[test]
+    fn formats_chat_payloads() {
+        let notification = notification();
+
+        assert_eq!(
+            MessageFormat::Slack.payload(&notification)["text"],
+            "*London: Chance of rain above 60%*\nChance of rain 85% at <Thu 14:00>"
+        );
+        assert_eq!(
+            MessageFormat::Discord.payload(&notification)["content"],
+            "**London: Chance of rain above 60%**\nChance of rain 85% at <Thu 14:00>"
+        );
+        assert_eq!(
+            MessageFormat::Matrix.payload(&notification)["formatted_body"],
+            "<b>London: Chance of rain above 60%</b><br>Chance of rain 85% at &lt;Thu 14:00&gt;"
+        );
+        assert_eq!(MessageFormat::Json.payload(&notification)["urgent"], false);
+    }
+
+    #[test]
+    fn posts_webhook_payload() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        // minimal HTTP server that answers a single request and hands back its body
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+
+            serde_json::from_slice::<Json>(&body).unwrap()
+        });
+
+        WebhookNotifier::new(&url, MessageFormat::Json)
+            .notify(&notification())
+            .unwrap();
+
+        assert_eq!(
+            server.join().unwrap(),
+            MessageFormat::Json.payload(&notification())
+        );
+    }
+
+    /// Fails the given number of times before succeeding
+    struct Flaky {
+        failures: Cell<u32>,
+        calls: Cell<u32>,
+    }
+
+    impl Notifier for Flaky {
+        fn notify(&self, _notification: &Notification) -> Result<(), NotifyError> {
+            self.calls.set(self.calls.get() + 1);
+            if self.failures.get() == 0 {
+                return Ok(());
+            }
+
+            self.failures.set(self.failures.get() - 1);
+            Err(NotifyError::Status(503))
+        }
+    }
+
+    #[test]
+    fn retries_failed_deliveries() {
+        let flaky = |failures| Flaky {
+            failures: Cell::new(failures),
+            calls: Cell::new(0),
+        };
+
+        let notifier = RetryNotifier::new(Box::new(flaky(2)), 3, Duration::ZERO);
+        assert!(notifier.notify(&notification()).is_ok());
+
+        let notifier = RetryNotifier::new(Box::new(flaky(3)), 3, Duration::ZERO);
+        assert!(matches!(
+            notifier.notify(&notification()),
+            Err(NotifyError::Status(503))
+        ));
+    }
+}
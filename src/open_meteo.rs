@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    api::ApiError,
+    weather::{Condition, Day, Forecast, Forecastday, Hour, Location, Weather},
+};
+
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1";
+const ARCHIVE_URL: &str = "https://archive-api.open-meteo.com/v1";
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1";
+const MARINE_URL: &str = "https://marine-api.open-meteo.com/v1";
+/// Environment variable replacing each of the URLs above, e.g. with the address of `t-weather mock-api`
+const URL_VARIABLE: &str = "OPEN_METEO_URL";
+
+/// Hourly variables read into `Hour`, the archive has no visibility, UV index or chance of
+/// precipitation so the forecast asks for those as well
+const HOURLY: &str = "temperature_2m,relative_humidity_2m,dew_point_2m,apparent_temperature,\
+precipitation,snowfall,weather_code,pressure_msl,cloud_cover,wind_speed_10m,wind_direction_10m,\
+wind_gusts_10m,is_day";
+const FORECAST_HOURLY: &str = "visibility,uv_index,precipitation_probability";
+const MARINE_HOURLY: &str =
+    "wave_height,swell_wave_height,swell_wave_direction,swell_wave_period,sea_surface_temperature";
+
+const KPH_PER_MPH: f64 = 1.609344;
+const MM_PER_INCH: f64 = 25.4;
+const FEET_PER_METRE: f64 = 3.28084;
+const METRES_PER_MILE: f64 = 1609.344;
+const INCHES_PER_MB: f64 = 0.02953;
+const COMPASS_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// Where requests to Open-Meteo's APIs are sent, they need no key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Client {
+    geocoding: String,
+    archive: String,
+    forecast: String,
+    marine: String,
+}
+
+/// Coordinates of a location and how to name it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Place {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    admin1: String,
+    #[serde(default)]
+    country: String,
+}
+
+/// Body of a geocoding search, `results` is left out when nothing matches
+#[derive(Deserialize)]
+struct Places {
+    #[serde(default)]
+    results: Vec<Place>,
+}
+
+/// Body of an unsuccessful response, e.g. {"error":true,"reason":"Parameter 'start_date' is out of allowed range"}
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: bool,
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    timezone: Tz,
+    hourly: T,
+}
+
+/// Columns of hourly values, missing values are null
+#[derive(Deserialize)]
+struct Hourly {
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f64>>,
+    relative_humidity_2m: Vec<Option<f64>>,
+    dew_point_2m: Vec<Option<f64>>,
+    apparent_temperature: Vec<Option<f64>>,
+    precipitation: Vec<Option<f64>>,
+    snowfall: Vec<Option<f64>>,
+    weather_code: Vec<Option<i64>>,
+    pressure_msl: Vec<Option<f64>>,
+    cloud_cover: Vec<Option<f64>>,
+    wind_speed_10m: Vec<Option<f64>>,
+    wind_direction_10m: Vec<Option<f64>>,
+    wind_gusts_10m: Vec<Option<f64>>,
+    is_day: Vec<Option<i64>>,
+    /// Metres, forecast only
+    #[serde(default)]
+    visibility: Vec<Option<f64>>,
+    #[serde(default)]
+    uv_index: Vec<Option<f64>>,
+    #[serde(default)]
+    precipitation_probability: Vec<Option<f64>>,
+}
+
+/// Columns of hourly sea state values, null on land
+#[derive(Deserialize)]
+struct MarineHourly {
+    time: Vec<String>,
+    wave_height: Vec<Option<f64>>,
+    swell_wave_height: Vec<Option<f64>>,
+    swell_wave_direction: Vec<Option<f64>>,
+    swell_wave_period: Vec<Option<f64>>,
+    sea_surface_temperature: Vec<Option<f64>>,
+}
+
+impl Client {
+    /// Open-Meteo, or OPEN_METEO_URL for every API when set
+    pub fn from_env() -> Client {
+        match std::env::var(URL_VARIABLE) {
+            Ok(url) => Client {
+                geocoding: url.clone(),
+                archive: url.clone(),
+                forecast: url.clone(),
+                marine: url,
+            },
+            Err(_) => Client {
+                geocoding: String::from(GEOCODING_URL),
+                archive: String::from(ARCHIVE_URL),
+                forecast: String::from(FORECAST_URL),
+                marine: String::from(MARINE_URL),
+            },
+        }
+    }
+
+    /// Recorded weather for each day from the first date to the last (inclusive), from the
+    /// archive of reanalysis data, which trails today by a few days
+    pub fn history(
+        &self,
+        location: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Weather, ApiError> {
+        let place = self.locate(location)?;
+        let response: Response<Hourly> = fetch(
+            &self.archive,
+            "archive",
+            vec![
+                ("latitude", place.latitude.to_string()),
+                ("longitude", place.longitude.to_string()),
+                ("start_date", from.format("%Y-%m-%d").to_string()),
+                ("end_date", to.format("%Y-%m-%d").to_string()),
+                ("hourly", String::from(HOURLY)),
+                ("timezone", String::from("auto")),
+            ],
+        )?;
+
+        let mut weather = weather_from(place, response.timezone, response.hourly.hours()?);
+        weather.fill_astro();
+
+        Ok(weather)
+    }
+
+    /// Wave, swell and sea temperature forecast for this many days, along with the weather
+    /// forecast for each hour. Open-Meteo has no tide times
+    pub fn marine(&self, location: &str, days: u8) -> Result<Weather, ApiError> {
+        let place = self.locate(location)?;
+        let params = |hourly: String| {
+            vec![
+                ("latitude", place.latitude.to_string()),
+                ("longitude", place.longitude.to_string()),
+                ("forecast_days", days.to_string()),
+                ("hourly", hourly),
+                ("timezone", String::from("auto")),
+            ]
+        };
+        let forecast: Response<Hourly> = fetch(
+            &self.forecast,
+            "forecast",
+            params(format!("{},{}", HOURLY, FORECAST_HOURLY)),
+        )?;
+        let sea: Response<MarineHourly> =
+            fetch(&self.marine, "marine", params(String::from(MARINE_HOURLY)))?;
+
+        let mut hours = forecast.hourly.hours()?;
+        sea.hourly.fill(&mut hours)?;
+        let mut weather = weather_from(place, forecast.timezone, hours);
+        weather.fill_astro();
+
+        Ok(weather)
+    }
+
+    /// Best match for a place name, or the coordinates of "lat,lon"
+    fn locate(&self, location: &str) -> Result<Place, ApiError> {
+        if let Some((lat, lon)) = location.split_once(',') {
+            if let (Ok(latitude), Ok(longitude)) = (lat.trim().parse(), lon.trim().parse()) {
+                return Ok(Place {
+                    name: location.to_string(),
+                    latitude,
+                    longitude,
+                    admin1: String::new(),
+                    country: String::new(),
+                });
+            }
+        }
+
+        let places: Places = fetch(
+            &self.geocoding,
+            "search",
+            vec![
+                ("name", location.to_string()),
+                ("count", String::from("1")),
+                ("format", String::from("json")),
+            ],
+        )?;
+
+        places
+            .results
+            .into_iter()
+            .next()
+            .ok_or_else(|| ApiError::OpenMeteo(format!("No location found for {}", location)))
+    }
+}
+
+fn fetch<T: DeserializeOwned>(
+    url: &str,
+    endpoint: &str,
+    params: Vec<(&str, String)>,
+) -> Result<T, ApiError> {
+    let full_url = format!("{}/{}", url.trim_end_matches('/'), endpoint);
+    let full_url = reqwest::Url::parse_with_params(&full_url, &params)
+        .map_err(|_| ApiError::InvalidUrl(url.to_string()))?;
+    let body = reqwest::blocking::get(full_url)?.text()?;
+
+    // errors are reported in the body alongside a 400 status
+    if let Ok(ErrorResponse {
+        error: true,
+        reason,
+    }) = serde_json::from_str(&body)
+    {
+        return Err(ApiError::OpenMeteo(reason));
+    }
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+impl Hourly {
+    /// Hours with a temperature, the most recent days of the archive are still null
+    fn hours(&self) -> Result<Vec<Hour>, ApiError> {
+        let mut hours = vec![];
+        for (i, time) in self.time.iter().enumerate() {
+            let time = parse_time(time)?;
+            let Some(temp_c) = value(&self.temperature_2m, i) else {
+                continue;
+            };
+
+            let feelslike_c = value(&self.apparent_temperature, i).unwrap_or(temp_c);
+            let dewpoint_c = value(&self.dew_point_2m, i).unwrap_or_default();
+            let wind_kph = value(&self.wind_speed_10m, i).unwrap_or_default();
+            let gust_kph = value(&self.wind_gusts_10m, i).unwrap_or_default();
+            let wind_degree = value(&self.wind_direction_10m, i).unwrap_or_default();
+            let pressure_mb = value(&self.pressure_msl, i).unwrap_or_default();
+            let precip_mm = value(&self.precipitation, i).unwrap_or_default();
+            let snow_cm = value(&self.snowfall, i).unwrap_or_default();
+            let vis_km = value(&self.visibility, i).unwrap_or_default() / 1000.0;
+            let will_it_rain = (precip_mm > 0.0 && snow_cm == 0.0) as i64;
+            let will_it_snow = (snow_cm > 0.0) as i64;
+            // without a forecast chance, what fell decides it
+            let (chance_of_rain, chance_of_snow) = match value(&self.precipitation_probability, i) {
+                Some(chance) if snow_cm > 0.0 => (0, chance.round() as i64),
+                Some(chance) => (chance.round() as i64, 0),
+                None => (will_it_rain * 100, will_it_snow * 100),
+            };
+
+            hours.push(Hour {
+                // filled in once the time zone is known
+                time_epoch: 0,
+                time,
+                temp_c,
+                temp_f: fahrenheit(temp_c),
+                is_day: value(&self.is_day, i).unwrap_or(1),
+                condition: condition(value(&self.weather_code, i).unwrap_or_default()),
+                wind_mph: rounded(wind_kph / KPH_PER_MPH, 1),
+                wind_kph,
+                wind_degree: wind_degree.round() as i64,
+                wind_dir: compass_point(wind_degree).to_string(),
+                pressure_mb,
+                pressure_in: rounded(pressure_mb * INCHES_PER_MB, 2),
+                precip_mm,
+                precip_in: rounded(precip_mm / MM_PER_INCH, 2),
+                snow_cm,
+                humidity: value(&self.relative_humidity_2m, i)
+                    .unwrap_or_default()
+                    .round() as i64,
+                cloud: value(&self.cloud_cover, i).unwrap_or_default().round() as i64,
+                feelslike_c,
+                feelslike_f: fahrenheit(feelslike_c),
+                windchill_c: feelslike_c.min(temp_c),
+                windchill_f: fahrenheit(feelslike_c.min(temp_c)),
+                heatindex_c: feelslike_c.max(temp_c),
+                heatindex_f: fahrenheit(feelslike_c.max(temp_c)),
+                dewpoint_c,
+                dewpoint_f: fahrenheit(dewpoint_c),
+                will_it_rain,
+                chance_of_rain,
+                will_it_snow,
+                chance_of_snow,
+                vis_km,
+                vis_miles: rounded(vis_km * 1000.0 / METRES_PER_MILE, 1),
+                gust_mph: rounded(gust_kph / KPH_PER_MPH, 1),
+                gust_kph,
+                uv: value(&self.uv_index, i).unwrap_or_default(),
+                ..Hour::default()
+            });
+        }
+
+        Ok(hours)
+    }
+}
+
+impl MarineHourly {
+    /// Adds the sea state to the hours at the same times
+    fn fill(&self, hours: &mut [Hour]) -> Result<(), ApiError> {
+        let mut index = HashMap::new();
+        for (i, time) in self.time.iter().enumerate() {
+            index.insert(parse_time(time)?, i);
+        }
+
+        for hour in hours {
+            let Some(&i) = index.get(&hour.time) else {
+                continue;
+            };
+            let swell_ht_mt = value(&self.swell_wave_height, i);
+            let swell_dir = value(&self.swell_wave_direction, i);
+            let water_temp_c = value(&self.sea_surface_temperature, i);
+
+            hour.sig_ht_mt = value(&self.wave_height, i);
+            hour.swell_ht_mt = swell_ht_mt;
+            hour.swell_ht_ft = swell_ht_mt.map(|metres| rounded(metres * FEET_PER_METRE, 2));
+            hour.swell_dir = swell_dir;
+            hour.swell_dir_16_point = swell_dir.map(|degrees| compass_point(degrees).to_string());
+            hour.swell_period_secs = value(&self.swell_wave_period, i);
+            hour.water_temp_c = water_temp_c;
+            hour.water_temp_f = water_temp_c.map(fahrenheit);
+        }
+
+        Ok(())
+    }
+}
+
+/// Open-Meteo's local times, e.g. 2024-05-01T13:00
+fn parse_time(time: &str) -> Result<NaiveDateTime, ApiError> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+        .map_err(|e| ApiError::Parse(serde::de::Error::custom(format!("time {}: {}", time, e))))
+}
+
+fn value<T: Copy>(values: &[Option<T>], i: usize) -> Option<T> {
+    values.get(i).copied().flatten()
+}
+
+/// Weather for the place with the hours grouped into days, in the place's time zone
+fn weather_from(place: Place, tz_id: Tz, hours: Vec<Hour>) -> Weather {
+    let now = Utc::now().with_timezone(&tz_id);
+    let location = Location {
+        name: place.name,
+        region: place.admin1,
+        country: place.country,
+        lat: place.latitude,
+        lon: place.longitude,
+        tz_id,
+        localtime_epoch: now.timestamp(),
+        localtime: now.naive_local(),
+    };
+
+    let mut forecastday: Vec<Forecastday> = vec![];
+    for mut hour in hours {
+        hour.time_epoch = location
+            .localize(hour.time)
+            .map_or(hour.time.and_utc().timestamp(), |time| time.timestamp());
+
+        let date = hour.time.date();
+        match forecastday.last_mut() {
+            Some(day) if day.date == date => day.hour.push(hour),
+            _ => forecastday.push(Forecastday {
+                date,
+                date_epoch: date.and_time(Default::default()).and_utc().timestamp(),
+                hour: vec![hour],
+                ..Forecastday::default()
+            }),
+        }
+    }
+    for day in &mut forecastday {
+        day.day = summarize(&day.hour);
+    }
+
+    Weather {
+        location,
+        forecast: Forecast { forecastday },
+        ..Weather::default()
+    }
+}
+
+/// Daily highs, lows, averages and totals of the hours, with the most severe condition
+fn summarize(hours: &[Hour]) -> Day {
+    let count = hours.len().max(1) as f64;
+    let max = |field: fn(&Hour) -> f64| hours.iter().map(field).fold(f64::MIN, f64::max);
+    let min = |field: fn(&Hour) -> f64| hours.iter().map(field).fold(f64::MAX, f64::min);
+    let sum = |field: fn(&Hour) -> f64| hours.iter().map(field).sum::<f64>();
+
+    let maxtemp_c = max(|hour| hour.temp_c);
+    let mintemp_c = min(|hour| hour.temp_c);
+    let avgtemp_c = rounded(sum(|hour| hour.temp_c) / count, 1);
+    let maxwind_kph = max(|hour| hour.wind_kph);
+    let totalprecip_mm = rounded(sum(|hour| hour.precip_mm), 2);
+    let avgvis_km = rounded(sum(|hour| hour.vis_km) / count, 1);
+    let daily_will_it_rain = hours.iter().any(|hour| hour.will_it_rain == 1) as i64;
+    let daily_will_it_snow = hours.iter().any(|hour| hour.will_it_snow == 1) as i64;
+
+    Day {
+        maxtemp_c,
+        maxtemp_f: fahrenheit(maxtemp_c),
+        mintemp_c,
+        mintemp_f: fahrenheit(mintemp_c),
+        avgtemp_c,
+        avgtemp_f: fahrenheit(avgtemp_c),
+        maxwind_mph: rounded(maxwind_kph / KPH_PER_MPH, 1),
+        maxwind_kph,
+        totalprecip_mm,
+        totalprecip_in: rounded(totalprecip_mm / MM_PER_INCH, 2),
+        totalsnow_cm: rounded(sum(|hour| hour.snow_cm), 2),
+        avgvis_km,
+        avgvis_miles: rounded(avgvis_km * 1000.0 / METRES_PER_MILE, 1),
+        avghumidity: (sum(|hour| hour.humidity as f64) / count).round() as i64,
+        daily_will_it_rain,
+        daily_chance_of_rain: hours
+            .iter()
+            .map(|hour| hour.chance_of_rain)
+            .max()
+            .unwrap_or(0),
+        daily_will_it_snow,
+        daily_chance_of_snow: hours
+            .iter()
+            .map(|hour| hour.chance_of_snow)
+            .max()
+            .unwrap_or(0),
+        condition: hours
+            .iter()
+            .map(|hour| &hour.condition)
+            .max_by_key(|condition| condition.code)
+            .cloned()
+            .unwrap_or_default(),
+        uv: max(|hour| hour.uv),
+        ..Day::default()
+    }
+}
+
+fn fahrenheit(celsius: f64) -> f64 {
+    rounded(celsius * 9.0 / 5.0 + 32.0, 1)
+}
+
+/// Converted values to as many decimals as weatherapi.com gives, e.g. 5.6 mph or 0.06 in
+fn rounded(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+
+    (value * scale).round() / scale
+}
+
+/// 16-point compass direction of a bearing in degrees, e.g. 200 is SSW
+fn compass_point(degrees: f64) -> &'static str {
+    let index = (degrees.rem_euclid(360.0) / 22.5).round() as usize % COMPASS_POINTS.len();
+
+    COMPASS_POINTS[index]
+}
+
+/// Condition matching a WMO weather code, with the nearest weatherapi.com code so it is drawn
+/// and summarised like weatherapi.com's. Codes rise with severity
+fn condition(wmo_code: i64) -> Condition {
+    let (code, text) = match wmo_code {
+        0 => (1000, "Clear sky"),
+        1 => (1000, "Mainly clear"),
+        2 => (1003, "Partly cloudy"),
+        3 => (1009, "Overcast"),
+        45 => (1135, "Fog"),
+        48 => (1147, "Depositing rime fog"),
+        51 => (1150, "Light drizzle"),
+        53 => (1153, "Moderate drizzle"),
+        55 => (1153, "Dense drizzle"),
+        56 => (1168, "Light freezing drizzle"),
+        57 => (1171, "Dense freezing drizzle"),
+        61 => (1183, "Slight rain"),
+        63 => (1189, "Moderate rain"),
+        65 => (1195, "Heavy rain"),
+        66 => (1198, "Light freezing rain"),
+        67 => (1201, "Heavy freezing rain"),
+        71 => (1213, "Slight snow fall"),
+        73 => (1219, "Moderate snow fall"),
+        75 => (1225, "Heavy snow fall"),
+        77 => (1237, "Snow grains"),
+        80 => (1240, "Slight rain showers"),
+        81 => (1243, "Moderate rain showers"),
+        82 => (1246, "Violent rain showers"),
+        85 => (1255, "Slight snow showers"),
+        86 => (1258, "Heavy snow showers"),
+        95 => (1276, "Thunderstorm"),
+        96 | 99 => (1282, "Thunderstorm with hail"),
+        _ => (0, "Unknown"),
+    };
+
+    Condition {
+        text: String::from(text),
+        icon: String::new(),
+        code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, UNKNOWN_LOCATION};
+
+    fn client() -> Client {
+        let url = mock::spawn();
+
+        Client {
+            geocoding: url.clone(),
+            archive: url.clone(),
+            forecast: url.clone(),
+            marine: url,
+        }
+    }
+
+    #[test]
+    fn fetches_history_from_the_archive() {
+        let client = client();
+        let from = NaiveDate::from_ymd_opt(2024, 4, 20).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 4, 22).unwrap();
+
+        let weather = client.history("London", from, to).unwrap();
+        assert_eq!(weather.location.region, "City of London, Greater London");
+        assert_eq!(weather.location.tz_id, chrono_tz::Europe::London);
+        assert!(weather.current.is_none());
+
+        let days = &weather.forecast.forecastday;
+        let dates: Vec<String> = days.iter().map(|day| day.date.to_string()).collect();
+        assert_eq!(dates, ["2024-04-20", "2024-04-21", "2024-04-22"]);
+        assert!(days[0].astro.sunrise.is_some());
+        assert_eq!(days[0].day.condition.text, "Moderate rain");
+        assert_eq!(days[0].day.maxtemp_c, 14.4);
+        assert_eq!(days[0].day.mintemp_c, 5.2);
+        assert_eq!(days[0].day.totalprecip_mm, 4.9);
+        assert_eq!(days[0].day.daily_will_it_rain, 1);
+
+        let hour = &days[0].hour[14];
+        assert_eq!(hour.time.to_string(), "2024-04-20 14:00:00");
+        // 14:00 BST
+        assert_eq!(hour.time_epoch, 1713618000);
+        assert_eq!(hour.precip_mm, 1.6);
+        assert_eq!(hour.chance_of_rain, 100);
+        assert_eq!(hour.wind_dir, "SW");
+        assert_eq!(hour.temp_f, fahrenheit(hour.temp_c));
+
+        let weather = client.history("51.5, -0.1", from, from).unwrap();
+        assert_eq!(weather.location.lat, 51.5);
+        assert_eq!(weather.forecast.forecastday.len(), 1);
+    }
+
+    #[test]
+    fn fetches_the_marine_forecast_with_the_weather() {
+        let weather = client().marine("London", 2).unwrap();
+
+        let days = &weather.forecast.forecastday;
+        let dates: Vec<String> = days.iter().map(|day| day.date.to_string()).collect();
+        assert_eq!(dates, ["2024-05-01", "2024-05-02"]);
+        assert_eq!(days[1].tides().count(), 0);
+        assert!(days[1].day.uv > 0.0);
+
+        let hour = &days[1].hour[12];
+        assert_eq!(hour.time.to_string(), "2024-05-02 12:00:00");
+        assert_eq!(hour.condition.text, "Light drizzle");
+        assert_eq!(hour.vis_km, 8.4);
+        assert_eq!(hour.chance_of_rain, 10);
+        assert_eq!(hour.sig_ht_mt, Some(0.6));
+        assert_eq!(hour.swell_ht_mt, Some(0.4));
+        assert_eq!(hour.swell_ht_ft, Some(1.31));
+        assert_eq!(hour.swell_dir_16_point.as_deref(), Some("WSW"));
+        assert_eq!(hour.swell_period_secs, Some(8.7));
+        assert_eq!(hour.water_temp_c, Some(11.4));
+        assert_eq!(hour.water_temp_f, Some(52.5));
+
+        assert!(matches!(
+            client().marine("London", 17),
+            Err(ApiError::OpenMeteo(_))
+        ));
+    }
+
+    #[test]
+    fn reports_unknown_locations_and_rejected_requests() {
+        let client = client();
+        let date = NaiveDate::from_ymd_opt(2024, 4, 20).unwrap();
+
+        assert!(matches!(
+            client.history(UNKNOWN_LOCATION, date, date),
+            Err(ApiError::OpenMeteo(_))
+        ));
+        assert!(matches!(
+            client.history("London", date, date - chrono::Duration::days(1)),
+            Err(ApiError::OpenMeteo(_))
+        ));
+    }
+
+    #[test]
+    fn maps_wmo_codes_and_bearings() {
+        assert_eq!(condition(63).kind(), crate::weather::ConditionKind::Rain);
+        assert_eq!(condition(95).kind(), crate::weather::ConditionKind::Thunder);
+        assert_eq!(condition(42).text, "Unknown");
+        assert_eq!(compass_point(0.0), "N");
+        assert_eq!(compass_point(350.0), "N");
+        assert_eq!(compass_point(225.0), "SW");
+    }
+}
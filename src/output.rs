@@ -0,0 +1,601 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+
+use crate::{
+    accuracy::{report, AccuracyReport},
+    activity::{best_windows, ActivityProfile, ActivityRanking},
+    advice::{advise, AdviceConfig},
+    api::{get_weather, Query},
+    derived::Derived,
+    export::{write_export, ExportOptions},
+    recorder::{record, Recorder},
+    summary::{summarize_day, Language},
+    weather::{AirQuality, Current, Weather},
+};
+
+/// How one-shot output is written to stdout
+pub enum Format {
+    Text,
+    Json,
+    /// Text with `{field}` placeholders, see `template_fields` for the available names
+    Template(String),
+}
+
+/// Prints the weather for the location and any active alerts to stdout, without starting the TUI
+/// Returns the process exit code: 0 when there are no alerts, 1 if the weather could not be
+/// fetched, otherwise a code reflecting the most severe alert (see `Severity::exit_code`)
+pub fn print_once(
+    location: &str,
+    query: &Query,
+    format: &Format,
+    language: Language,
+    recorder: Option<&Recorder>,
+) -> i32 {
+    let weather = match get_weather(location, query) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Unable to fetch weather for {}: {}", location, e);
+            return 1;
+        }
+    };
+    record(recorder, location, &weather);
+
+    match format {
+        Format::Text => {
+            match query {
+                Query::Forecast(_) => {
+                    println!("{}", weather);
+
+                    for day in &weather.forecast.forecastday {
+                        println!("{}: {}", day.date, summarize_day(day, language));
+                    }
+                }
+                // historical and long-range data have no current conditions, so summarise each day
+                Query::History(..) | Query::Future(_) => {
+                    println!("{}", weather.location.name);
+                    if let Query::Future(_) = query {
+                        println!("Long-range forecast, lower confidence than the 14 day forecast");
+                    }
+
+                    for day in &weather.forecast.forecastday {
+                        println!(
+                            "{}: {} {}, {}C / {}C",
+                            day.date,
+                            day.day.condition.kind().icon(),
+                            day.day.condition.text,
+                            day.day.mintemp_c,
+                            day.day.maxtemp_c
+                        );
+                    }
+                }
+                Query::Marine(..) => {
+                    println!("{}", weather.location.name);
+
+                    for day in &weather.forecast.forecastday {
+                        let tides: Vec<String> = day
+                            .tides()
+                            .map(|tide| {
+                                format!(
+                                    "{} {} ({}m)",
+                                    tide.tide_type,
+                                    tide.tide_time.format("%H:%M"),
+                                    tide.tide_height_mt
+                                )
+                            })
+                            .collect();
+                        match tides.is_empty() {
+                            true => println!("{}: no tide times", day.date),
+                            false => println!("{}: {}", day.date, tides.join(", ")),
+                        }
+                    }
+                }
+            }
+
+            for alert in &weather.alerts.alert {
+                println!("{}", alert);
+            }
+        }
+        Format::Json => match serde_json::to_string_pretty(&with_derived(&weather)) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Unable to serialize weather: {}", e);
+                return 1;
+            }
+        },
+        Format::Template(template) => println!("{}", render_template(template, &weather)),
+    }
+
+    weather
+        .alerts
+        .max_severity()
+        .map_or(0, |severity| severity.exit_code())
+}
+
+/// Prints what to bring for each upcoming advice window
+/// Returns the process exit code, 1 if the weather could not be fetched
+pub fn print_advice(location: &str, query: &Query, config: &AdviceConfig) -> i32 {
+    let weather = match get_weather(location, query) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Unable to fetch weather for {}: {}", location, e);
+            return 1;
+        }
+    };
+
+    println!("{}", weather.location.name);
+    for advice in advise(&weather, config) {
+        println!(
+            "{} {} ({}):",
+            advice.date,
+            advice.window.name,
+            advice.window.label()
+        );
+
+        if advice.recommendations.is_empty() {
+            println!("  Nothing extra needed");
+        }
+        for (recommendation, reason) in &advice.recommendations {
+            println!("  {} {}: {}", recommendation.icon(), recommendation, reason);
+        }
+    }
+
+    0
+}
+
+/// Prints the best upcoming windows for each activity, as text or JSON
+/// Returns the process exit code, 1 if the weather could not be fetched
+pub fn print_activities(
+    location: &str,
+    query: &Query,
+    profiles: &[ActivityProfile],
+    limit: usize,
+    json: bool,
+) -> i32 {
+    let weather = match get_weather(location, query) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Unable to fetch weather for {}: {}", location, e);
+            return 1;
+        }
+    };
+
+    let rankings: Vec<ActivityRanking> = profiles
+        .iter()
+        .map(|profile| ActivityRanking {
+            activity: profile.name.clone(),
+            windows: best_windows(&weather, profile, limit),
+        })
+        .collect();
+
+    if json {
+        return match serde_json::to_string_pretty(&rankings) {
+            Ok(json) => {
+                println!("{}", json);
+                0
+            }
+            Err(e) => {
+                eprintln!("Unable to serialize activities: {}", e);
+                1
+            }
+        };
+    }
+
+    println!("{}", weather.location.name);
+    for ranking in rankings {
+        println!("{}:", ranking.activity);
+        for window in ranking.windows {
+            println!(
+                "  {} - {}  {}/100",
+                window.start.format("%a %d %b %H:%M"),
+                window.end.format("%H:%M"),
+                window.score
+            );
+        }
+    }
+
+    0
+}
+
+/// Prints the daily highs, lows and rain totals recorded for the location, as text or JSON
+/// Returns the process exit code, 1 if the database could not be read
+pub fn print_recorded(
+    location: &str,
+    recorder: &Recorder,
+    from: NaiveDate,
+    to: NaiveDate,
+    json: bool,
+) -> i32 {
+    let days = match recorder.daily(location, from, to) {
+        Ok(days) => days,
+        Err(e) => {
+            eprintln!(
+                "Unable to read {}: {}",
+                recorder.path().to_string_lossy(),
+                e
+            );
+            return 1;
+        }
+    };
+
+    if json {
+        return match serde_json::to_string_pretty(&days) {
+            Ok(json) => {
+                println!("{}", json);
+                0
+            }
+            Err(e) => {
+                eprintln!("Unable to serialize recorded weather: {}", e);
+                1
+            }
+        };
+    }
+
+    if days.is_empty() {
+        println!("Nothing recorded for {} from {} to {}", location, from, to);
+        return 0;
+    }
+
+    println!("Recorded weather for {}", location);
+    for day in days {
+        println!(
+            "{}: high {}C, low {}C, rain {:.1}mm ({} observations)",
+            day.date, day.high_c, day.low_c, day.rain_mm, day.observations
+        );
+    }
+
+    0
+}
+
+/// Prints temperature and rain accuracy for each location's recorded forecasts, as text or JSON
+/// Returns the process exit code, 1 if the database could not be read
+pub fn print_accuracy(recorder: &Recorder, locations: &[String], json: bool) -> i32 {
+    let mut reports: Vec<AccuracyReport> = vec![];
+    for location in locations {
+        match report(recorder, location) {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                eprintln!(
+                    "Unable to read {}: {}",
+                    recorder.path().to_string_lossy(),
+                    e
+                );
+                return 1;
+            }
+        }
+    }
+
+    if json {
+        return match serde_json::to_string_pretty(&reports) {
+            Ok(json) => {
+                println!("{}", json);
+                0
+            }
+            Err(e) => {
+                eprintln!("Unable to serialize accuracy: {}", e);
+                1
+            }
+        };
+    }
+
+    for report in reports {
+        println!("{}", report.location);
+        if report.stats.is_empty() {
+            println!("  No recorded forecasts have been observed yet");
+            continue;
+        }
+
+        println!(
+            "  {:<16} {:>5} {:>8} {:>9} {:>10} {:>9}",
+            "Provider", "Ahead", "Samples", "Temp MAE", "Temp bias", "Rain hits"
+        );
+        for stat in &report.stats {
+            println!(
+                "  {:<16} {:>4}d {:>8} {:>8.1}C {:>+9.1}C {:>8.0}%",
+                stat.provider,
+                stat.lead_days,
+                stat.samples,
+                stat.temp_mae_c,
+                stat.temp_bias_c,
+                stat.rain_hit_rate * 100.0
+            );
+        }
+    }
+
+    0
+}
+
+/// Writes the hourly forecast as CSV or NDJSON, or the daily forecast as iCalendar, to the file
+/// in the options or stdout
+/// Returns the process exit code, 1 if the weather could not be fetched or written
+pub fn print_export(
+    location: &str,
+    query: &Query,
+    options: &ExportOptions,
+    language: Language,
+) -> i32 {
+    let weather = match get_weather(location, query) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Unable to fetch weather for {}: {}", location, e);
+            return 1;
+        }
+    };
+
+    let writer: Box<dyn Write> = match &options.path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Unable to create {}: {}", path.to_string_lossy(), e);
+                return 1;
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+
+    match write_export(&weather, options, language, writer) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Replaces each `{field}` in the template with its value, unknown fields are left untouched
+pub fn render_template(template: &str, weather: &Weather) -> String {
+    let mut result = template.to_string();
+
+    for (name, value) in template_fields(weather) {
+        result = result.replace(&format!("{{{}}}", name), &value);
+    }
+
+    result
+}
+
+/// Weather as JSON, with a `derived` object added to the current conditions and each hour
+fn with_derived(weather: &Weather) -> Value {
+    let mut value = serde_json::to_value(weather).unwrap_or_default();
+    if let Some(current) = &weather.current {
+        value["current"]["derived"] = derived_json(&current.derived());
+    }
+    for (i, day) in weather.forecast.forecastday.iter().enumerate() {
+        for (j, hour) in day.hour.iter().enumerate() {
+            value["forecast"]["forecastday"][i]["hour"][j]["derived"] =
+                derived_json(&hour.derived());
+        }
+    }
+
+    value
+}
+
+/// Derived metrics named like their template fields, rounded the same way
+fn derived_json(derived: &Derived) -> Value {
+    let rounded = |value: f64| (value * 10.0).round() / 10.0;
+
+    json!({
+        "apparent_c": rounded(derived.apparent_temp_c),
+        "wet_bulb_c": rounded(derived.wet_bulb_c),
+        "absolute_humidity": rounded(derived.absolute_humidity),
+        "cloud_base_m": derived.cloud_base_m.round(),
+        "beaufort": derived.beaufort.0,
+        "beaufort_desc": derived.beaufort.description(),
+        "thermal_stress": derived.thermal_stress.to_string(),
+    })
+}
+
+/// Names and values of the fields available to templates. Every field is always there, those
+/// without data are empty, e.g. the current conditions for history and long-range queries
+fn template_fields(weather: &Weather) -> Vec<(&'static str, String)> {
+    let current = weather.current.as_ref();
+    let field = |value: fn(&Current) -> String| current.map(value).unwrap_or_default();
+    let derived = current.map(Current::derived);
+    let derived_field =
+        |value: fn(&Derived) -> String| derived.as_ref().map(value).unwrap_or_default();
+
+    let mut fields = vec![
+        ("location", weather.location.name.clone()),
+        ("region", weather.location.region.clone()),
+        ("country", weather.location.country.clone()),
+        (
+            "localtime",
+            weather
+                .location
+                .localtime
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+        ),
+        ("condition", field(|current| current.condition.text.clone())),
+        (
+            "condition_icon",
+            field(|current| current.condition.kind().icon().to_string()),
+        ),
+        (
+            "condition_class",
+            field(|current| current.condition.kind().class().to_string()),
+        ),
+        ("temp_c", field(|current| current.temp_c.to_string())),
+        ("temp_f", field(|current| current.temp_f.to_string())),
+        (
+            "feelslike_c",
+            field(|current| current.feelslike_c.to_string()),
+        ),
+        (
+            "feelslike_f",
+            field(|current| current.feelslike_f.to_string()),
+        ),
+        ("humidity", field(|current| current.humidity.to_string())),
+        ("wind_kph", field(|current| current.wind_kph.to_string())),
+        ("wind_mph", field(|current| current.wind_mph.to_string())),
+        ("wind_dir", field(|current| current.wind_dir.clone())),
+        (
+            "pressure_mb",
+            field(|current| current.pressure_mb.to_string()),
+        ),
+        ("precip_mm", field(|current| current.precip_mm.to_string())),
+        ("uv", field(|current| current.uv.to_string())),
+        ("alerts", weather.alerts.alert.len().to_string()),
+        (
+            "apparent_c",
+            derived_field(|derived| format!("{:.1}", derived.apparent_temp_c)),
+        ),
+        (
+            "wet_bulb_c",
+            derived_field(|derived| format!("{:.1}", derived.wet_bulb_c)),
+        ),
+        (
+            "absolute_humidity",
+            derived_field(|derived| format!("{:.1}", derived.absolute_humidity)),
+        ),
+        (
+            "cloud_base_m",
+            derived_field(|derived| format!("{:.0}", derived.cloud_base_m)),
+        ),
+        (
+            "beaufort",
+            derived_field(|derived| derived.beaufort.0.to_string()),
+        ),
+        (
+            "beaufort_desc",
+            derived_field(|derived| derived.beaufort.description().to_string()),
+        ),
+        (
+            "thermal_stress",
+            derived_field(|derived| derived.thermal_stress.to_string()),
+        ),
+    ];
+
+    let tendency = weather.pressure_tendency();
+    fields.extend([
+        (
+            "pressure_change",
+            tendency
+                .map(|(change, _)| format!("{:.1}", change))
+                .unwrap_or_default(),
+        ),
+        (
+            "pressure_tendency",
+            tendency
+                .map(|(_, tendency)| tendency.to_string())
+                .unwrap_or_default(),
+        ),
+    ]);
+
+    // forecast values for the hour closest to the location's local time
+    let hour = weather.nearest_hour(weather.location.localtime);
+    fields.extend([
+        (
+            "chance_of_rain",
+            hour.map(|hour| hour.chance_of_rain.to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "chance_of_snow",
+            hour.map(|hour| hour.chance_of_snow.to_string())
+                .unwrap_or_default(),
+        ),
+    ]);
+
+    let air_quality = current.and_then(|current| current.air_quality.as_ref());
+    let air_field = |value: fn(&AirQuality) -> String| air_quality.map(value).unwrap_or_default();
+    fields.extend([
+        ("co", air_field(|air_quality| air_quality.co.to_string())),
+        ("no2", air_field(|air_quality| air_quality.no2.to_string())),
+        ("o3", air_field(|air_quality| air_quality.o3.to_string())),
+        ("so2", air_field(|air_quality| air_quality.so2.to_string())),
+        (
+            "pm2_5",
+            air_field(|air_quality| air_quality.pm2_5.to_string()),
+        ),
+        (
+            "pm10",
+            air_field(|air_quality| air_quality.pm10.to_string()),
+        ),
+        (
+            "us_epa_index",
+            air_field(|air_quality| air_quality.us_epa_index.to_string()),
+        ),
+        (
+            "us_epa_category",
+            air_field(|air_quality| {
+                air_quality
+                    .epa_category()
+                    .map(|category| category.to_string())
+                    .unwrap_or_default()
+            }),
+        ),
+        (
+            "gb_defra_index",
+            air_field(|air_quality| air_quality.gb_defra_index.to_string()),
+        ),
+        (
+            "gb_defra_band",
+            air_field(|air_quality| {
+                air_quality
+                    .defra_band()
+                    .map(|band| band.to_string())
+                    .unwrap_or_default()
+            }),
+        ),
+    ]);
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn renders_current_fields_empty_without_current_conditions() {
+        let mut weather = fixture_weather();
+        let template = "{location}: {condition} {temp_c}C, feels like {apparent_c}C";
+        assert_eq!(
+            render_template(template, &weather),
+            "London: Partly cloudy 11C, feels like 7.8C"
+        );
+
+        weather.current = None;
+        assert_eq!(
+            render_template(template, &weather),
+            "London:  C, feels like C"
+        );
+    }
+
+    #[test]
+    fn adds_derived_metrics_to_json() {
+        let weather = fixture_weather();
+        let json = with_derived(&weather);
+
+        assert_eq!(json["current"]["derived"]["apparent_c"], 7.8);
+        let hour = &json["forecast"]["forecastday"][0]["hour"][12];
+        assert_eq!(hour["derived"]["cloud_base_m"], 500.0);
+        assert_eq!(hour["derived"]["beaufort_desc"], "Gentle breeze");
+        assert_eq!(hour["derived"]["thermal_stress"], "No thermal stress");
+    }
+
+    #[test]
+    fn renders_every_field_for_history() {
+        let history: Weather =
+            serde_json::from_str(include_str!("../tests/fixtures/history.json")).unwrap();
+        let names: Vec<&str> = template_fields(&fixture_weather())
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(names.contains(&"pm2_5"));
+        assert!(names.contains(&"pressure_tendency"));
+
+        let template: String = names.iter().map(|name| format!("{{{}}}", name)).collect();
+        let rendered = render_template(&template, &history);
+        assert!(!rendered.contains('{'), "{}", rendered);
+        assert_eq!(
+            render_template("{pm2_5}|{us_epa_category}|{temp_c}", &history),
+            "||"
+        );
+    }
+}
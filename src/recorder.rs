@@ -0,0 +1,376 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::weather::Weather;
+
+/// Stored with every row, so observations and forecasts from other providers can sit alongside
+pub const PROVIDER: &str = "weatherapi.com";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS observations (
+    location TEXT NOT NULL COLLATE NOCASE,
+    provider TEXT NOT NULL,
+    observed_at INTEGER NOT NULL,
+    local_time TEXT NOT NULL,
+    temp_c REAL NOT NULL,
+    feelslike_c REAL NOT NULL,
+    humidity INTEGER NOT NULL,
+    pressure_mb REAL NOT NULL,
+    wind_kph REAL NOT NULL,
+    gust_kph REAL NOT NULL,
+    precip_mm REAL NOT NULL,
+    uv REAL NOT NULL,
+    cloud INTEGER NOT NULL,
+    condition TEXT NOT NULL,
+    PRIMARY KEY (location, provider, observed_at)
+);
+CREATE TABLE IF NOT EXISTS forecasts (
+    location TEXT NOT NULL COLLATE NOCASE,
+    provider TEXT NOT NULL,
+    issued_at INTEGER NOT NULL,
+    time_epoch INTEGER NOT NULL,
+    local_time TEXT NOT NULL,
+    temp_c REAL NOT NULL,
+    chance_of_rain INTEGER NOT NULL,
+    precip_mm REAL NOT NULL,
+    wind_kph REAL NOT NULL,
+    condition TEXT NOT NULL,
+    PRIMARY KEY (location, provider, issued_at, time_epoch)
+);
+";
+
+/// Whether fetched weather is recorded, and where
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RecorderConfig {
+    pub enabled: bool,
+    /// Defaults to $XDG_DATA_HOME/t-weather/weather.db, falling back to ~/.local/share
+    pub path: Option<PathBuf>,
+}
+
+/// SQLite database of every observation and forecast fetched, per location
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum RecorderError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+}
+
+/// Current conditions as recorded
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Observation {
+    pub observed_at: i64,
+    pub local_time: NaiveDateTime,
+    pub temp_c: f64,
+    pub precip_mm: f64,
+}
+
+/// Recorded observations for one day, in the location's time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyRecord {
+    pub date: NaiveDate,
+    pub high_c: f64,
+    pub low_c: f64,
+    /// Largest reading in each hour, summed over the day
+    pub rain_mm: f64,
+    pub observations: u32,
+}
+
+impl RecorderConfig {
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.clone().or_else(default_path)
+    }
+}
+
+impl fmt::Display for RecorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecorderError::Io(e) => write!(f, "Unable to create the database directory: {}", e),
+            RecorderError::Sqlite(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for RecorderError {
+    fn from(e: io::Error) -> Self {
+        RecorderError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for RecorderError {
+    fn from(e: rusqlite::Error) -> Self {
+        RecorderError::Sqlite(e)
+    }
+}
+
+impl Recorder {
+    /// The recorder set in the config, if recording is turned on
+    pub fn new(config: &RecorderConfig) -> Option<Recorder> {
+        match config.enabled {
+            true => config.path().map(Recorder::open),
+            false => None,
+        }
+    }
+
+    pub fn open(path: PathBuf) -> Recorder {
+        Recorder { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn connect(&self) -> Result<Connection, RecorderError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(&self.path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(connection)
+    }
+
+    /// Saves the current conditions and the hourly forecast from the current hour onwards.
+    /// Weather already recorded is skipped, as is weather without current conditions (history, marine)
+    pub fn record(&self, location: &str, weather: &Weather) -> Result<(), RecorderError> {
+        let Some(current) = &weather.current else {
+            return Ok(());
+        };
+
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT OR IGNORE INTO observations (location, provider, observed_at, local_time,
+                temp_c, feelslike_c, humidity, pressure_mb, wind_kph, gust_kph, precip_mm, uv,
+                cloud, condition)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                location,
+                PROVIDER,
+                current.last_updated_epoch,
+                current.last_updated,
+                current.temp_c,
+                current.feelslike_c,
+                current.humidity,
+                current.pressure_mb,
+                current.wind_kph,
+                current.gust_kph,
+                current.precip_mm,
+                current.uv,
+                current.cloud,
+                current.condition.text,
+            ],
+        )?;
+
+        {
+            let mut insert = transaction.prepare(
+                "INSERT OR IGNORE INTO forecasts (location, provider, issued_at, time_epoch,
+                    local_time, temp_c, chance_of_rain, precip_mm, wind_kph, condition)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            // hours that have already ended are as much observation as forecast
+            for hour in weather
+                .hours()
+                .filter(|hour| hour.time_epoch + 3600 > current.last_updated_epoch)
+            {
+                insert.execute(params![
+                    location,
+                    PROVIDER,
+                    current.last_updated_epoch,
+                    hour.time_epoch,
+                    hour.time,
+                    hour.temp_c,
+                    hour.chance_of_rain,
+                    hour.precip_mm,
+                    hour.wind_kph,
+                    hour.condition.text,
+                ])?;
+            }
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Highs, lows and rain totals for each day from `from` to `to` (inclusive) with observations
+    pub fn daily(
+        &self,
+        location: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DailyRecord>, RecorderError> {
+        let connection = self.connect()?;
+        let mut statement = connection.prepare(
+            "SELECT date, MAX(high), MIN(low), SUM(rain), SUM(count) FROM (
+                SELECT substr(local_time, 1, 10) AS date, substr(local_time, 12, 2) AS hour,
+                    MAX(temp_c) AS high, MIN(temp_c) AS low, MAX(precip_mm) AS rain,
+                    COUNT(*) AS count
+                FROM observations
+                WHERE location = ?1 AND provider = ?2
+                    AND substr(local_time, 1, 10) BETWEEN ?3 AND ?4
+                GROUP BY date, hour
+             )
+             GROUP BY date
+             ORDER BY date",
+        )?;
+
+        let records = statement
+            .query_map(params![location, PROVIDER, from, to], |row| {
+                Ok(DailyRecord {
+                    date: row.get(0)?,
+                    high_c: row.get(1)?,
+                    low_c: row.get(2)?,
+                    rain_mm: row.get(3)?,
+                    observations: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<DailyRecord>, rusqlite::Error>>()?;
+
+        Ok(records)
+    }
+
+    /// Observations made at or after `since` (a Unix timestamp), oldest first
+    pub fn observations_since(
+        &self,
+        location: &str,
+        since: i64,
+    ) -> Result<Vec<Observation>, RecorderError> {
+        let connection = self.connect()?;
+        let mut statement = connection.prepare(
+            "SELECT observed_at, local_time, temp_c, precip_mm FROM observations
+             WHERE location = ?1 AND provider = ?2 AND observed_at >= ?3
+             ORDER BY observed_at",
+        )?;
+
+        let observations = statement
+            .query_map(params![location, PROVIDER, since], |row| {
+                Ok(Observation {
+                    observed_at: row.get(0)?,
+                    local_time: row.get(1)?,
+                    temp_c: row.get(2)?,
+                    precip_mm: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<Observation>, rusqlite::Error>>()?;
+
+        Ok(observations)
+    }
+}
+
+/// Records the weather when recording is turned on, reporting failures without stopping the caller
+pub fn record(recorder: Option<&Recorder>, location: &str, weather: &Weather) {
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.record(location, weather) {
+            eprintln!("Unable to record weather for {}: {}", location, e);
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+
+    Some(data_dir.join("t-weather").join("weather.db"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    fn recorder(name: &str) -> Recorder {
+        let path = env::temp_dir().join(format!("t-weather-{}-{}.db", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        Recorder::open(path)
+    }
+
+    /// The fixture's current conditions, `minutes` later and `delta` degrees warmer
+    fn observed(weather: &Weather, minutes: i64, delta: f64, precip_mm: f64) -> Weather {
+        let mut weather = weather.clone();
+        let current = weather.current.as_mut().unwrap();
+        current.last_updated_epoch += minutes * 60;
+        current.last_updated += Duration::minutes(minutes);
+        current.temp_c += delta;
+        current.precip_mm = precip_mm;
+        weather
+    }
+
+    #[test]
+    fn aggregates_recorded_observations() {
+        let weather = fixture_weather();
+        let recorder = recorder("daily");
+        let current = weather.current.clone().unwrap();
+        let date = current.last_updated.date();
+
+        recorder
+            .record("London", &observed(&weather, 0, 0.0, 0.5))
+            .unwrap();
+        // recording the same weather twice keeps one observation
+        recorder
+            .record("London", &observed(&weather, 0, 0.0, 0.5))
+            .unwrap();
+        recorder
+            .record("london", &observed(&weather, 15, 3.0, 1.0))
+            .unwrap();
+        recorder
+            .record("London", &observed(&weather, -60, -2.0, 0.25))
+            .unwrap();
+
+        // the fixture is observed at 09:00, so all three fall on the same day
+        let days = recorder.daily("London", date, date).unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].date, date);
+        assert_eq!(days[0].observations, 3);
+        assert_eq!(days[0].high_c, current.temp_c + 3.0);
+        assert_eq!(days[0].low_c, current.temp_c - 2.0);
+        // 1.0 is the largest reading from 09:00, 0.25 the only one from 08:00
+        assert_eq!(days[0].rain_mm, 1.25);
+
+        let since = current.last_updated_epoch;
+        let observations = recorder.observations_since("LONDON", since).unwrap();
+        assert_eq!(observations.len(), 2);
+        assert_eq!(observations[0].temp_c, current.temp_c);
+
+        let forecasts: i64 = recorder
+            .connect()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM forecasts WHERE issued_at = ?1",
+                [since],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let upcoming = weather
+            .hours()
+            .filter(|hour| hour.time_epoch + 3600 > since)
+            .count();
+        assert_eq!(forecasts as usize, upcoming);
+        let _ = fs::remove_file(recorder.path());
+    }
+
+    #[test]
+    fn skips_weather_without_current_conditions() {
+        let mut weather = fixture_weather();
+        weather.current = None;
+        let recorder = recorder("history");
+
+        recorder.record("London", &weather).unwrap();
+        let observations = recorder.observations_since("London", 0).unwrap();
+        assert!(observations.is_empty());
+        let _ = fs::remove_file(recorder.path());
+    }
+}
@@ -0,0 +1,608 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::Write,
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    api::{ApiError, Query},
+    cache::Cache,
+    calendar::render_calendar,
+    recorder::{record, Recorder},
+    summary::Language,
+    weather::{Current, Hour, Location, Weather},
+};
+
+/// Days fetched each refresh, so the forecast hours don't run out before midnight
+const SERVE_FORECAST_DAYS: u8 = 2;
+/// Longest forecast weatherapi.com returns
+const MAX_FORECAST_DAYS: u8 = 14;
+/// Days in the calendar feed unless ?days= is given
+const CALENDAR_DAYS: u8 = 3;
+/// Error code weatherapi.com returns when no location matches the query
+const NO_MATCHING_LOCATION: i64 = 1006;
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Address, locations and refresh schedule for the `serve` subcommand
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    pub address: String,
+    /// Served instead of --location when set
+    pub locations: Vec<String>,
+    /// Never shorter than the cache TTL
+    pub refresh_minutes: u64,
+    /// Hours ahead exported as forecast gauges
+    pub forecast_hours: usize,
+    /// Requests each client address may make to the REST API per minute, 0 for no limit
+    pub rate_limit_per_minute: u32,
+}
+
+/// Latest weather for each location, keyed by the name it was requested with
+pub type Latest = Arc<RwLock<BTreeMap<String, Weather>>>;
+
+/// Fixed one-minute windows of requests per client address
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    clients: HashMap<IpAddr, (Instant, u32)>,
+}
+
+/// Status, content type and body sent back for a request
+#[derive(Debug, PartialEq)]
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+/// Body of /v1/current, the forecast without the forecast
+#[derive(Serialize)]
+struct CurrentResponse<'a> {
+    location: &'a Location,
+    current: Option<&'a Current>,
+}
+
+/// Value exported for each location's current conditions and forecast hours
+struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    current: fn(&Current) -> f64,
+    hour: fn(&Hour) -> f64,
+}
+
+const GAUGES: [Gauge; 10] = [
+    Gauge {
+        name: "temperature_celsius",
+        help: "Air temperature",
+        current: |current| current.temp_c,
+        hour: |hour| hour.temp_c,
+    },
+    Gauge {
+        name: "feels_like_celsius",
+        help: "Feels-like temperature",
+        current: |current| current.feelslike_c,
+        hour: |hour| hour.feelslike_c,
+    },
+    Gauge {
+        name: "humidity_percent",
+        help: "Relative humidity",
+        current: |current| current.humidity as f64,
+        hour: |hour| hour.humidity as f64,
+    },
+    Gauge {
+        name: "pressure_hpa",
+        help: "Air pressure",
+        current: |current| current.pressure_mb,
+        hour: |hour| hour.pressure_mb,
+    },
+    Gauge {
+        name: "wind_speed_kph",
+        help: "Wind speed",
+        current: |current| current.wind_kph,
+        hour: |hour| hour.wind_kph,
+    },
+    Gauge {
+        name: "wind_gust_kph",
+        help: "Wind gust speed",
+        current: |current| current.gust_kph,
+        hour: |hour| hour.gust_kph,
+    },
+    Gauge {
+        name: "precipitation_mm",
+        help: "Precipitation",
+        current: |current| current.precip_mm,
+        hour: |hour| hour.precip_mm,
+    },
+    Gauge {
+        name: "uv_index",
+        help: "UV index",
+        current: |current| current.uv,
+        hour: |hour| hour.uv,
+    },
+    Gauge {
+        name: "cloud_cover_percent",
+        help: "Cloud cover",
+        current: |current| current.cloud as f64,
+        hour: |hour| hour.cloud as f64,
+    },
+    Gauge {
+        name: "visibility_km",
+        help: "Visibility",
+        current: |current| current.vis_km,
+        hour: |hour| hour.vis_km,
+    },
+];
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            address: String::from("127.0.0.1:9898"),
+            locations: vec![],
+            refresh_minutes: 15,
+            forecast_hours: 3,
+            rate_limit_per_minute: 60,
+        }
+    }
+}
+
+impl ServeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        // with a zero cache TTL, refreshing without a pause would call the API nonstop
+        if self.refresh_minutes < 1 {
+            return Err(String::from("serve refresh_minutes must be at least 1"));
+        }
+
+        Ok(())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limit_per_minute: u32) -> RateLimiter {
+        RateLimiter {
+            limit: limit_per_minute,
+            window: Duration::from_secs(60),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Counts a request from the client, or returns how long until it may make another
+    pub fn check(&mut self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        // forget clients whose window has ended, so the map doesn't grow with every address seen
+        let window = self.window;
+        self.clients
+            .retain(|_, (start, _)| now.duration_since(*start) < window);
+
+        let (start, count) = self.clients.entry(client).or_insert((now, 0));
+        match *count < self.limit {
+            true => {
+                *count += 1;
+                Ok(())
+            }
+            false => Err(window - now.duration_since(*start)),
+        }
+    }
+}
+
+impl Reply {
+    fn json<T: Serialize>(value: &T) -> Reply {
+        match serde_json::to_string(value) {
+            Ok(body) => Reply {
+                status: 200,
+                content_type: JSON_CONTENT_TYPE,
+                body,
+            },
+            Err(e) => Reply::error(500, &e.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Reply {
+        Reply {
+            status,
+            content_type: JSON_CONTENT_TYPE,
+            body: serde_json::json!({ "error": message }).to_string(),
+        }
+    }
+
+    fn days_error() -> Reply {
+        Reply::error(
+            400,
+            &format!("days must be between 1 and {}", MAX_FORECAST_DAYS),
+        )
+    }
+
+    fn api_error(e: ApiError) -> Reply {
+        let status = match e {
+            ApiError::MissingKey | ApiError::InvalidUrl(_) => 500,
+            ApiError::Provider {
+                code: NO_MATCHING_LOCATION,
+                ..
+            } => 404,
+            ApiError::Provider { .. }
+            | ApiError::OpenMeteo(_)
+            | ApiError::Request(_)
+            | ApiError::Parse(_) => 502,
+        };
+
+        Reply::error(status, &e.to_string())
+    }
+}
+
+/// Keeps the latest weather for each location, refetching through the cache in the background
+pub fn refresh_in_background(
+    config: &ServeConfig,
+    locations: &[String],
+    cache: Cache,
+    recorder: Option<Recorder>,
+) -> Latest {
+    let latest: Latest = Arc::default();
+    let interval = Duration::from_secs(config.refresh_minutes * 60).max(cache.ttl());
+    let query = Query::Forecast(SERVE_FORECAST_DAYS);
+    let locations = locations.to_vec();
+    let shared = Arc::clone(&latest);
+
+    thread::spawn(move || loop {
+        for location in &locations {
+            match cache.get_weather(location, &query) {
+                Ok(weather) => {
+                    record(recorder.as_ref(), location, &weather);
+                    if let Ok(mut latest) = shared.write() {
+                        latest.insert(location.clone(), weather);
+                    }
+                }
+                // the last successful fetch keeps being served
+                Err(e) => eprintln!("Unable to fetch weather for {}: {}", location, e),
+            }
+        }
+
+        thread::sleep(interval);
+    });
+
+    latest
+}
+
+/// Serves the REST API, and Prometheus metrics at /metrics when `latest` is given, until the process is stopped.
+/// Every request is logged to stdout
+pub fn serve(
+    config: &ServeConfig,
+    cache: &Cache,
+    latest: Option<Latest>,
+    language: Language,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(&config.address)?;
+    println!("Serving the weather API at http://{}/v1", config.address);
+    if latest.is_some() {
+        println!("Serving metrics at http://{}/metrics", config.address);
+    }
+
+    let mut limiter = RateLimiter::new(config.rate_limit_per_minute);
+    for request in server.incoming_requests() {
+        let started = Instant::now();
+        let client = request.remote_addr().map(|addr| addr.ip());
+        let (path, params) = parse_url(request.url());
+
+        let mut retry_after = None;
+        let reply = match (request.method(), path.as_str()) {
+            (Method::Get, "/metrics") => match &latest {
+                Some(latest) => Reply {
+                    status: 200,
+                    content_type: METRICS_CONTENT_TYPE,
+                    body: match latest.read() {
+                        Ok(latest) => render_metrics(&latest, config.forecast_hours),
+                        Err(_) => String::new(),
+                    },
+                },
+                None => Reply::error(404, "Not found"),
+            },
+            (Method::Get, path) if path.starts_with("/v1/") => {
+                match client.map(|client| limiter.check(client, started)) {
+                    Some(Err(wait)) => {
+                        retry_after = Some(wait.as_secs().max(1));
+                        Reply::error(429, "Too many requests")
+                    }
+                    _ => route(cache, path, &params, language),
+                }
+            }
+            (Method::Get, _) => Reply::error(404, "Not found"),
+            _ => Reply::error(405, "Method not allowed"),
+        };
+
+        println!(
+            "{} {} \"{} {}\" {} {}ms",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%z"),
+            client.map_or(String::from("-"), |client| client.to_string()),
+            request.method(),
+            request.url(),
+            reply.status,
+            started.elapsed().as_millis()
+        );
+
+        let content_type =
+            Header::from_bytes("Content-Type", reply.content_type).expect("static header is valid");
+        let mut response = Response::from_string(reply.body)
+            .with_status_code(reply.status)
+            .with_header(content_type);
+        if let Some(seconds) = retry_after {
+            response.add_header(
+                Header::from_bytes("Retry-After", seconds.to_string()).expect("number is valid"),
+            );
+        }
+
+        if let Err(e) = request.respond(response) {
+            eprintln!("Unable to respond: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers a REST API request with weather in the same model the --json output uses,
+/// or as an iCalendar feed
+fn route(cache: &Cache, path: &str, params: &HashMap<String, String>, language: Language) -> Reply {
+    let Some(q) = params.get("q").filter(|q| !q.trim().is_empty()) else {
+        return Reply::error(400, "Missing q parameter, e.g. ?q=London");
+    };
+
+    match path {
+        "/v1/current" => match cache.get_weather(q, &Query::Forecast(1)) {
+            Ok(weather) => Reply::json(&CurrentResponse {
+                location: &weather.location,
+                current: weather.current.as_ref(),
+            }),
+            Err(e) => Reply::api_error(e),
+        },
+        "/v1/forecast" => match forecast_days(params, 1) {
+            Some(days) => match cache.get_weather(q, &Query::Forecast(days)) {
+                Ok(weather) => Reply::json(&weather),
+                Err(e) => Reply::api_error(e),
+            },
+            None => Reply::days_error(),
+        },
+        "/v1/calendar.ics" => match forecast_days(params, CALENDAR_DAYS) {
+            Some(days) => match cache.get_weather(q, &Query::Forecast(days)) {
+                Ok(weather) => Reply {
+                    status: 200,
+                    content_type: CALENDAR_CONTENT_TYPE,
+                    body: render_calendar(&weather, language, Utc::now()),
+                },
+                Err(e) => Reply::api_error(e),
+            },
+            None => Reply::days_error(),
+        },
+        "/v1/search" => match cache.search(q) {
+            Ok(results) => Reply::json(&results),
+            Err(e) => Reply::api_error(e),
+        },
+        _ => Reply::error(404, "Not found"),
+    }
+}
+
+/// The days parameter, or `default` when it isn't given. None when it is out of range
+fn forecast_days(params: &HashMap<String, String>, default: u8) -> Option<u8> {
+    match params.get("days").map(|days| days.parse::<u8>()) {
+        None => Some(default),
+        Some(Ok(days)) if (1..=MAX_FORECAST_DAYS).contains(&days) => Some(days),
+        Some(_) => None,
+    }
+}
+
+/// Path and decoded query parameters of a request target such as /v1/forecast?q=New%20York&days=3
+pub(crate) fn parse_url(target: &str) -> (String, HashMap<String, String>) {
+    match reqwest::Url::parse("http://localhost").and_then(|base| base.join(target)) {
+        Ok(url) => (
+            url.path().to_string(),
+            url.query_pairs().into_owned().collect(),
+        ),
+        Err(_) => (target.to_string(), HashMap::new()),
+    }
+}
+
+/// Prometheus text exposition of the current conditions and the next `forecast_hours` hours
+pub fn render_metrics(latest: &BTreeMap<String, Weather>, forecast_hours: usize) -> String {
+    let mut metrics = String::new();
+
+    for gauge in &GAUGES {
+        let name = format!("weather_{}", gauge.name);
+        let _ = writeln!(metrics, "# HELP {} {}", name, gauge.help);
+        let _ = writeln!(metrics, "# TYPE {} gauge", name);
+        for (location, current) in current_conditions(latest) {
+            let _ = writeln!(
+                metrics,
+                "{}{{location=\"{}\"}} {}",
+                name,
+                escape_label(location),
+                (gauge.current)(current)
+            );
+        }
+
+        let name = format!("weather_forecast_{}", gauge.name);
+        let _ = writeln!(
+            metrics,
+            "# HELP {} Forecast {}",
+            name,
+            gauge.help.to_lowercase()
+        );
+        let _ = writeln!(metrics, "# TYPE {} gauge", name);
+        for (location, weather) in latest {
+            // the first hour is the current one, which the current conditions already cover
+            for (hours_ahead, hour) in weather.next_n_hours(forecast_hours + 1).enumerate().skip(1)
+            {
+                let _ = writeln!(
+                    metrics,
+                    "{}{{location=\"{}\",hours_ahead=\"{}\"}} {}",
+                    name,
+                    escape_label(location),
+                    hours_ahead,
+                    (gauge.hour)(hour)
+                );
+            }
+        }
+    }
+
+    let name = "weather_last_updated_timestamp_seconds";
+    let _ = writeln!(
+        metrics,
+        "# HELP {} When the provider last updated the current conditions",
+        name
+    );
+    let _ = writeln!(metrics, "# TYPE {} gauge", name);
+    for (location, current) in current_conditions(latest) {
+        let _ = writeln!(
+            metrics,
+            "{}{{location=\"{}\"}} {}",
+            name,
+            escape_label(location),
+            current.last_updated_epoch
+        );
+    }
+
+    metrics
+}
+
+/// Locations with current conditions, and those conditions
+fn current_conditions(
+    latest: &BTreeMap<String, Weather>,
+) -> impl Iterator<Item = (&String, &Current)> {
+    latest
+        .iter()
+        .filter_map(|(location, weather)| Some((location, weather.current.as_ref()?)))
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn validates_refresh_minutes() {
+        assert!(ServeConfig::default().validate().is_ok());
+        let zero = ServeConfig {
+            refresh_minutes: 0,
+            ..ServeConfig::default()
+        };
+        assert!(zero.validate().is_err());
+    }
+
+    #[test]
+    fn renders_gauges_per_location() {
+        let weather = fixture_weather();
+        let latest = BTreeMap::from([(String::from("London \"UK\""), weather.clone())]);
+
+        let metrics = render_metrics(&latest, 3);
+        assert!(metrics.contains("# TYPE weather_temperature_celsius gauge\n"));
+        assert!(metrics.contains(&format!(
+            "weather_temperature_celsius{{location=\"London \\\"UK\\\"\"}} {}\n",
+            weather.current.as_ref().unwrap().temp_c
+        )));
+
+        let forecast: Vec<&str> = metrics
+            .lines()
+            .filter(|line| line.starts_with("weather_forecast_uv_index{"))
+            .collect();
+        assert_eq!(forecast.len(), 3);
+        assert!(forecast[0].contains("hours_ahead=\"1\""));
+        assert!(forecast[2].contains("hours_ahead=\"3\""));
+    }
+
+    #[test]
+    fn limits_requests_per_client() {
+        let mut limiter = RateLimiter::new(2);
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(client, now), Ok(()));
+        assert_eq!(limiter.check(client, now), Ok(()));
+        assert_eq!(
+            limiter.check(client, now + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert_eq!(limiter.check(other, now), Ok(()));
+        // a new window starts once the minute is up
+        assert_eq!(limiter.check(client, now + Duration::from_secs(60)), Ok(()));
+
+        let mut unlimited = RateLimiter::new(0);
+        assert!((0..100).all(|_| unlimited.check(client, now).is_ok()));
+    }
+
+    #[test]
+    fn parses_request_targets() {
+        let (path, params) = parse_url("/v1/forecast?q=New%20York&days=3");
+        assert_eq!(path, "/v1/forecast");
+        assert_eq!(params.get("q").map(String::as_str), Some("New York"));
+        assert_eq!(params.get("days").map(String::as_str), Some("3"));
+    }
+
+    #[test]
+    fn answers_each_location_from_its_own_cache() {
+        let dir = std::env::temp_dir().join(format!("t-weather-serve-{}", std::process::id()));
+        let cache = Cache::new(&crate::cache::CacheConfig {
+            ttl_minutes: 15,
+            dir: Some(dir.clone()),
+        });
+        for (location, name) in [("北京", "Beijing"), ("東京", "Tokyo")] {
+            let mut weather = fixture_weather();
+            weather.location.name = name.to_string();
+            cache.seed(location, &Query::Forecast(1), &weather);
+        }
+
+        for (location, name) in [("北京", "Beijing"), ("東京", "Tokyo")] {
+            let params = HashMap::from([(String::from("q"), location.to_string())]);
+            for path in ["/v1/current", "/v1/forecast"] {
+                let reply = route(&cache, path, &params, Language::En);
+                assert_eq!(reply.status, 200, "{}", reply.body);
+                let body: serde_json::Value = serde_json::from_str(&reply.body).unwrap();
+                assert_eq!(body["location"]["name"], name);
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_invalid_requests_before_fetching() {
+        let cache = Cache::new(&crate::cache::CacheConfig::default());
+
+        let reply = route(&cache, "/v1/current", &HashMap::new(), Language::En);
+        assert_eq!(reply.status, 400);
+        assert!(reply.body.contains("Missing q parameter"));
+
+        let (_, params) = parse_url("/v1/forecast?q=London&days=15");
+        assert_eq!(
+            route(&cache, "/v1/forecast", &params, Language::En).status,
+            400
+        );
+        let (_, params) = parse_url("/v1/calendar.ics?q=London&days=0");
+        assert_eq!(
+            route(&cache, "/v1/calendar.ics", &params, Language::En).status,
+            400
+        );
+
+        let (_, params) = parse_url("/v1/unknown?q=London");
+        assert_eq!(
+            route(&cache, "/v1/unknown", &params, Language::En).status,
+            404
+        );
+    }
+}
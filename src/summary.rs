@@ -0,0 +1,333 @@
+use chrono::{NaiveDateTime, Timelike};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::weather::{ConditionKind, Forecastday, Hour};
+
+/// Hours the opening phrase compares, the sky is described as clearing if it turns clear later on
+const MORNING_START: u32 = 6;
+const MORNING_END: u32 = 12;
+const EVENING_END: u32 = 21;
+
+/// Chance of precipitation (%) worth mentioning, and the chance from which it is expected
+const MENTION_CHANCE: i64 = 20;
+const LIKELY_CHANCE: i64 = 50;
+
+/// Gust speeds (km/h) from which the wind is described as breezy, windy or stormy
+const BREEZY_GUST_KPH: f64 = 40.0;
+const WINDY_GUST_KPH: f64 = 60.0;
+const STORMY_GUST_KPH: f64 = 90.0;
+
+/// Language summaries are written in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+    Es,
+}
+
+enum Precipitation {
+    Showers,
+    Rain,
+    Snow,
+}
+
+enum Wind {
+    Breezy,
+    Windy,
+    Stormy,
+}
+
+/// Describes a forecast day in a sentence, e.g.
+/// "Cloudy morning clearing by 2pm, high of 18°C, 70% chance of showers after 6pm, breezy with gusts to 45 km/h"
+pub fn summarize_day(day: &Forecastday, language: Language) -> String {
+    let mut phrases = vec![sky(day, language), language.high(day.day.maxtemp_c)];
+
+    if let Some(phrase) = precipitation(&day.hour, language) {
+        phrases.push(phrase);
+    }
+
+    let max_gust = day
+        .hour
+        .iter()
+        .map(|hour| hour.gust_kph)
+        .fold(0.0, f64::max);
+    let wind = match max_gust {
+        gust if gust >= STORMY_GUST_KPH => Some(Wind::Stormy),
+        gust if gust >= WINDY_GUST_KPH => Some(Wind::Windy),
+        gust if gust >= BREEZY_GUST_KPH => Some(Wind::Breezy),
+        _ => None,
+    };
+    if let Some(wind) = wind {
+        phrases.push(language.wind(wind, max_gust.round()));
+    }
+
+    capitalize(&phrases.join(", "))
+}
+
+/// Condition for the morning, noting when it clears up later in the day
+fn sky(day: &Forecastday, language: Language) -> String {
+    let daytime: Vec<&Hour> = day
+        .hour
+        .iter()
+        .filter(|hour| (MORNING_START..EVENING_END).contains(&hour.time.hour()))
+        .collect();
+    let morning: Vec<&Hour> = daytime
+        .iter()
+        .copied()
+        .filter(|hour| hour.time.hour() < MORNING_END)
+        .collect();
+
+    let Some(morning_kind) = most_common_kind(&morning) else {
+        return language.day(day.day.condition.kind());
+    };
+
+    if is_clear(morning_kind) {
+        return language.day(morning_kind);
+    }
+
+    let clearing = daytime
+        .iter()
+        .skip_while(|hour| hour.time.hour() < MORNING_END - 1)
+        .find(|hour| is_clear(hour.condition.kind()));
+
+    match clearing {
+        Some(hour) => language.clearing(morning_kind, hour.time),
+        None => language.morning(morning_kind),
+    }
+}
+
+/// Chance of rain or snow, from the first hour it becomes likely
+fn precipitation(hours: &[Hour], language: Language) -> Option<String> {
+    let chance = |hour: &Hour| hour.chance_of_rain.max(hour.chance_of_snow);
+    let wettest = hours.iter().max_by_key(|hour| chance(hour))?;
+    let max_chance = chance(wettest);
+
+    if max_chance < MENTION_CHANCE {
+        return None;
+    }
+
+    let kind = if wettest.chance_of_snow > wettest.chance_of_rain {
+        Precipitation::Snow
+    } else if wettest.condition.kind() == ConditionKind::HeavyRain {
+        Precipitation::Rain
+    } else {
+        Precipitation::Showers
+    };
+    let after = hours
+        .iter()
+        .find(|hour| chance(hour) >= LIKELY_CHANCE.min(max_chance))
+        .map(|hour| hour.time)
+        // a start at midnight covers the whole day, so there is nothing to add
+        .filter(|time| time.hour() > 0);
+
+    Some(language.precipitation(max_chance, kind, after))
+}
+
+fn most_common_kind(hours: &[&Hour]) -> Option<ConditionKind> {
+    let mut best: Option<(ConditionKind, usize)> = None;
+
+    for hour in hours {
+        let kind = hour.condition.kind();
+        let count = hours
+            .iter()
+            .filter(|other| other.condition.kind() == kind)
+            .count();
+
+        if best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((kind, count));
+        }
+    }
+
+    best.map(|(kind, _)| kind)
+}
+
+fn is_clear(kind: ConditionKind) -> bool {
+    matches!(kind, ConditionKind::Clear | ConditionKind::PartlyCloudy)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl Language {
+    /// Adjective describing a condition, e.g. "cloudy"
+    fn adjective(&self, kind: ConditionKind) -> &'static str {
+        match self {
+            Language::En => match kind {
+                ConditionKind::Clear => "sunny",
+                ConditionKind::PartlyCloudy => "partly cloudy",
+                ConditionKind::Cloudy => "cloudy",
+                ConditionKind::Overcast => "overcast",
+                ConditionKind::Fog => "foggy",
+                ConditionKind::Drizzle => "drizzly",
+                ConditionKind::Rain => "rainy",
+                ConditionKind::HeavyRain => "very wet",
+                ConditionKind::Sleet => "sleety",
+                ConditionKind::Snow => "snowy",
+                ConditionKind::HeavySnow => "very snowy",
+                ConditionKind::Hail => "hail-prone",
+                ConditionKind::Thunder => "stormy",
+                ConditionKind::Unknown => "changeable",
+            },
+            Language::De => match kind {
+                ConditionKind::Clear => "sonniger",
+                ConditionKind::PartlyCloudy => "teils bewölkter",
+                ConditionKind::Cloudy => "bewölkter",
+                ConditionKind::Overcast => "bedeckter",
+                ConditionKind::Fog => "nebliger",
+                ConditionKind::Drizzle => "nieseliger",
+                ConditionKind::Rain => "regnerischer",
+                ConditionKind::HeavyRain => "sehr nasser",
+                ConditionKind::Sleet => "graupeliger",
+                ConditionKind::Snow => "verschneiter",
+                ConditionKind::HeavySnow => "stark verschneiter",
+                ConditionKind::Hail => "hageliger",
+                ConditionKind::Thunder => "gewittriger",
+                ConditionKind::Unknown => "wechselhafter",
+            },
+            Language::Es => match kind {
+                ConditionKind::Clear => "soleado",
+                ConditionKind::PartlyCloudy => "parcialmente nublado",
+                ConditionKind::Cloudy => "nublado",
+                ConditionKind::Overcast => "cubierto",
+                ConditionKind::Fog => "con niebla",
+                ConditionKind::Drizzle => "con llovizna",
+                ConditionKind::Rain => "lluvioso",
+                ConditionKind::HeavyRain => "muy lluvioso",
+                ConditionKind::Sleet => "con aguanieve",
+                ConditionKind::Snow => "nevado",
+                ConditionKind::HeavySnow => "muy nevado",
+                ConditionKind::Hail => "con granizo",
+                ConditionKind::Thunder => "tormentoso",
+                ConditionKind::Unknown => "variable",
+            },
+        }
+    }
+
+    fn day(&self, kind: ConditionKind) -> String {
+        match self {
+            Language::En => format!("{} day", self.adjective(kind)),
+            Language::De => format!("{} Tag", self.adjective(kind)),
+            Language::Es => format!("día {}", self.adjective(kind)),
+        }
+    }
+
+    fn morning(&self, kind: ConditionKind) -> String {
+        match self {
+            Language::En => format!("{} morning", self.adjective(kind)),
+            Language::De => format!("{} Morgen", self.adjective(kind)),
+            Language::Es => format!("{} por la mañana", self.adjective(kind)),
+        }
+    }
+
+    fn clearing(&self, kind: ConditionKind, time: NaiveDateTime) -> String {
+        match self {
+            Language::En => format!("{} clearing by {}", self.morning(kind), self.time(time)),
+            Language::De => format!("{}, ab {} aufklarend", self.morning(kind), self.time(time)),
+            Language::Es => format!(
+                "{}, despejando hacia las {}",
+                self.morning(kind),
+                self.time(time)
+            ),
+        }
+    }
+
+    fn high(&self, temp_c: f64) -> String {
+        match self {
+            Language::En => format!("high of {}°C", temp_c.round()),
+            Language::De => format!("Höchstwert {}°C", temp_c.round()),
+            Language::Es => format!("máxima de {}°C", temp_c.round()),
+        }
+    }
+
+    fn precipitation(
+        &self,
+        chance: i64,
+        kind: Precipitation,
+        after: Option<NaiveDateTime>,
+    ) -> String {
+        let (what, after_word) = match (self, kind) {
+            (Language::En, Precipitation::Showers) => ("showers", "after"),
+            (Language::En, Precipitation::Rain) => ("rain", "after"),
+            (Language::En, Precipitation::Snow) => ("snow", "after"),
+            (Language::De, Precipitation::Showers) => ("Schauer", "ab"),
+            (Language::De, Precipitation::Rain) => ("Regen", "ab"),
+            (Language::De, Precipitation::Snow) => ("Schnee", "ab"),
+            (Language::Es, Precipitation::Showers) => ("chubascos", "a partir de las"),
+            (Language::Es, Precipitation::Rain) => ("lluvia", "a partir de las"),
+            (Language::Es, Precipitation::Snow) => ("nieve", "a partir de las"),
+        };
+        let chance = match self {
+            Language::En => format!("{}% chance of {}", chance, what),
+            Language::De => format!("{}% Wahrscheinlichkeit für {}", chance, what),
+            Language::Es => format!("{}% de probabilidad de {}", chance, what),
+        };
+
+        match after {
+            Some(time) => format!("{} {} {}", chance, after_word, self.time(time)),
+            None => chance,
+        }
+    }
+
+    fn wind(&self, wind: Wind, gust_kph: f64) -> String {
+        let description = match (self, wind) {
+            (Language::En, Wind::Breezy) => "breezy",
+            (Language::En, Wind::Windy) => "windy",
+            (Language::En, Wind::Stormy) => "stormy",
+            (Language::De, Wind::Breezy) => "frisch",
+            (Language::De, Wind::Windy) => "windig",
+            (Language::De, Wind::Stormy) => "stürmisch",
+            (Language::Es, Wind::Breezy) => "brisa",
+            (Language::Es, Wind::Windy) => "ventoso",
+            (Language::Es, Wind::Stormy) => "tempestuoso",
+        };
+
+        match self {
+            Language::En => format!("{} with gusts to {} km/h", description, gust_kph),
+            Language::De => format!("{} mit Böen bis {} km/h", description, gust_kph),
+            Language::Es => format!("{} con rachas de hasta {} km/h", description, gust_kph),
+        }
+    }
+
+    fn time(&self, time: NaiveDateTime) -> String {
+        match self {
+            Language::En => time.format("%-I%P").to_string(),
+            Language::De => time.format("%-H Uhr").to_string(),
+            Language::Es => time.format("%-H:%M").to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    #[test]
+    fn summarizes_rainy_day() {
+        let weather = fixture_weather();
+        let summary = summarize_day(&weather.forecast.forecastday[1], Language::En);
+
+        assert!(summary.starts_with(char::is_uppercase), "{}", summary);
+        assert!(summary.contains("high of"), "{}", summary);
+        assert!(summary.contains("chance of"), "{}", summary);
+        assert!(summary.contains("after 2pm"), "{}", summary);
+    }
+
+    #[test]
+    fn localizes_summary() {
+        let weather = fixture_weather();
+        let day = &weather.forecast.forecastday[1];
+
+        assert!(summarize_day(day, Language::De).contains("Höchstwert"));
+        assert!(summarize_day(day, Language::Es).contains("máxima de"));
+    }
+}
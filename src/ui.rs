@@ -1,3 +1,4 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
 use crossterm::{
     event::{read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent},
     execute,
@@ -6,33 +7,72 @@ use crossterm::{
 use std::io::{self, Stdout};
 use tui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
-    widgets::{Block, BorderType, Borders, Cell, List, ListItem, Paragraph, Row, Table},
-    Terminal,
+    symbols,
+    text::{Span, Spans},
+    widgets::{
+        Axis, Block, BorderType, Borders, Cell, Chart, Dataset, Gauge, GraphType, List, ListItem,
+        Paragraph, Row, Table, Wrap,
+    },
+    Frame, Terminal,
 };
 
 use crate::{
-    api::get_current_weather,
+    activity::{best_windows, ActivityProfile},
+    advice::{advise, AdviceConfig},
+    api::{get_weather, Query},
+    astronomy::Twilight,
+    config::Config,
+    day_parts::{summarize, DayPart},
+    recorder::{Observation, Recorder},
+    summary::{summarize_day, Language},
     utils::read_file,
-    weather::{get_data_for_days, Weather},
+    weather::{ConditionKind, DefraBand, EpaCategory, Forecastday, Severity, Weather},
 };
 
 struct AppState {
     is_search_active: bool,
     input: String,
     weather: Weather,
+    query: Query,
+    config: Config,
+    recorder: Option<Recorder>,
+    /// Observations recorded for the location over the last `RECORDED_HOURS`
+    recorded: Vec<Observation>,
 }
 
 impl AppState {
-    fn new(weather: Weather) -> AppState {
+    fn new(weather: Weather, query: Query, config: Config) -> AppState {
+        let recorder = Recorder::new(&config.recorder);
+
         AppState {
             is_search_active: false,
             input: String::new(),
             weather,
+            query,
+            config,
+            recorder,
+            recorded: vec![],
         }
     }
 
+    /// Records the weather for the location and reloads what has been recorded for it.
+    /// Errors are ignored, since they can't be printed over the TUI
+    fn record(&mut self, location: &str) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+
+        let _ = recorder.record(location, &self.weather);
+        self.recorded = match &self.weather.current {
+            Some(current) => recorder
+                .observations_since(location, current.last_updated_epoch - RECORDED_HOURS * 3600)
+                .unwrap_or_default(),
+            None => vec![],
+        };
+    }
+
     fn update_input(&mut self, c: char) {
         self.input.push(c);
     }
@@ -67,17 +107,17 @@ impl TerminalState {
 
 #[derive(PartialEq, Eq)]
 enum Input {
-    QUIT,
-    SEARCH,
-    DOWN,
-    UP,
-    CHAR,
-    ESCAPE,
-    REMOVE,
-    ENTER,
+    Quit,
+    Search,
+    Down,
+    Up,
+    Char,
+    Escape,
+    Remove,
+    Enter,
 }
 
-pub fn start(location: &str) -> Result<(), io::Error> {
+pub fn start(location: &str, query: Query, config: Config) -> Result<(), io::Error> {
     enable_raw_mode()?;
 
     let mut terminal_state = TerminalState::new().unwrap();
@@ -87,22 +127,48 @@ pub fn start(location: &str) -> Result<(), io::Error> {
         EnableMouseCapture
     )?;
 
-    let weather = match get_current_weather(location, None) {
-        Some(data) => data,
-        None => {
+    let weather = match get_weather(location, &query) {
+        Ok(data) => data,
+        Err(e) => {
             let _ = restore(&mut terminal_state.terminal);
+            eprintln!("{}", e);
             return Ok(());
         }
     };
 
-    let mut app_state = AppState::new(weather);
+    // historical and long-range data have no current conditions, alerts or air quality
+    let mut items = match query {
+        Query::Forecast(_) => vec![
+            "Current",
+            "Forecast",
+            "Advice",
+            "Activity",
+            "Astronomy",
+            "Air Quality",
+            "Alerts",
+        ],
+        Query::History(..) => vec!["History", "Astronomy"],
+        Query::Future(_) => vec!["Long-range", "Astronomy"],
+        Query::Marine(..) => vec!["Marine", "Forecast", "Astronomy"],
+    };
 
-    let items = vec!["Current", "Forecast"];
+    let mut app_state = AppState::new(weather, query, config);
+    app_state.record(location);
     let mut selected_index: usize = 0;
 
+    // recorded observations can only be shown when recording is on
+    if app_state.recorder.is_some() && matches!(app_state.query, Query::Forecast(_)) {
+        items.insert(2, "Recorded");
+    }
+
     loop {
         terminal_state.terminal.draw(|rect| {
             let size = rect.size();
+            // only make room for the alert banner when there is something to show
+            let banner_height = match app_state.weather.alerts.is_empty() {
+                true => 0,
+                false => 3,
+            };
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .margin(2)
@@ -110,6 +176,7 @@ pub fn start(location: &str) -> Result<(), io::Error> {
                     [
                         Constraint::Length(7),
                         Constraint::Length(3),
+                        Constraint::Length(banner_height),
                         Constraint::Min(2),
                         Constraint::Length(3),
                     ]
@@ -131,21 +198,66 @@ pub fn start(location: &str) -> Result<(), io::Error> {
             let horizontal_layout = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(25), Constraint::Percentage(75)].as_ref())
-                .split(chunks[2]);
+                .split(chunks[3]);
 
             // list weather forecast options
             let menu = render_menu(&items, selected_index);
 
-            // weather data (current / forecast)
-            let data = &app_state.weather.to_string();
-
             // change data display based on sub-menu selection
-            match &items[selected_index] {
-                // display the forecast data for upcoming week
-                &"Forecast" => {}
+            match items[selected_index] {
+                // display the forecast/recorded data for each day
+                "Forecast" | "History" | "Long-range" => {
+                    let days: Vec<&Forecastday> =
+                        app_state.weather.forecast.forecastday.iter().collect();
+                    let temperatures = temperature_points(&days);
+                    let precipitation = precipitation_points(&days);
+
+                    let mut constraints = vec![Constraint::Percentage(40)];
+                    constraints.extend(
+                        days.iter()
+                            .map(|_| Constraint::Ratio(1, days.len().max(1) as u32)),
+                    );
+
+                    let day_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(constraints)
+                        .split(horizontal_layout[1]);
+
+                    rect.render_widget(
+                        render_temperature_chart(&temperatures, &precipitation, &days),
+                        day_chunks[0],
+                    );
+                    for (i, day) in days.iter().enumerate() {
+                        let title = match app_state.query {
+                            Query::Future(_) => {
+                                format!("{} (long-range forecast, lower confidence)", day.date)
+                            }
+                            _ => day.date.to_string(),
+                        };
+                        rect.render_widget(
+                            render_table(
+                                title,
+                                &app_state.weather,
+                                day.date,
+                                &app_state.config.day_parts,
+                            ),
+                            day_chunks[i + 1],
+                        );
+                    }
+                }
+                // display recorded observations leading up to the forecast
+                "Recorded" => {
+                    let recorded = recorded_points(&app_state.weather, &app_state.recorded);
+                    let forecast = upcoming_points(&app_state.weather);
+
+                    rect.render_widget(
+                        render_recorded_chart(&recorded, &forecast),
+                        horizontal_layout[1],
+                    );
+                }
                 // display the forecast data for today
-                &"Current" => {
-                    let current = render_forecast(&data);
+                "Current" => {
+                    let current = render_forecast(&app_state.weather);
 
                     let table_chunks = Layout::default()
                         .direction(Direction::Vertical)
@@ -153,62 +265,149 @@ pub fn start(location: &str) -> Result<(), io::Error> {
                             [Constraint::Percentage(35), Constraint::Percentage(65)].as_ref(),
                         )
                         .split(horizontal_layout[1]);
+                    let current_chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            [Constraint::Percentage(40), Constraint::Percentage(60)].as_ref(),
+                        )
+                        .split(table_chunks[0]);
+
+                    rect.render_widget(current, current_chunks[0]);
+                    rect.render_widget(render_next_hours(&app_state.weather), current_chunks[1]);
+                    if let Some(today) = app_state.weather.current_day() {
+                        rect.render_widget(
+                            render_table(
+                                String::from("Today's Forecast"),
+                                &app_state.weather,
+                                today.date,
+                                &app_state.config.day_parts,
+                            ),
+                            table_chunks[1],
+                        )
+                    }
+                }
+                // display wave/swell conditions and tides
+                "Marine" => {
+                    let days: Vec<&Forecastday> =
+                        app_state.weather.forecast.forecastday.iter().collect();
+                    let tides = tide_points(&days);
+
+                    let marine_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(
+                            [Constraint::Percentage(40), Constraint::Percentage(60)].as_ref(),
+                        )
+                        .split(horizontal_layout[1]);
+                    let table_chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            [Constraint::Percentage(65), Constraint::Percentage(35)].as_ref(),
+                        )
+                        .split(marine_chunks[1]);
+
+                    rect.render_widget(render_tide_chart(&tides, &days), marine_chunks[0]);
+                    if let Some(today) = days.first() {
+                        rect.render_widget(render_marine_table(today), table_chunks[0]);
+                        rect.render_widget(render_tide_times(today), table_chunks[1]);
+                    }
+                }
+                // display sun and moon data for today
+                "Astronomy" => {
+                    let astro_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(10), Constraint::Length(3)].as_ref())
+                        .split(horizontal_layout[1]);
 
-                    rect.render_widget(current, table_chunks[0]);
+                    rect.render_widget(render_astronomy(&app_state.weather), astro_chunks[0]);
+                    rect.render_widget(render_sun_progress(&app_state.weather), astro_chunks[1]);
+                }
+                // display pollutant levels and air quality indices
+                "Air Quality" => {
+                    rect.render_widget(
+                        render_air_quality(&app_state.weather),
+                        horizontal_layout[1],
+                    );
+                }
+                // display what to bring for the upcoming advice windows
+                "Advice" => {
                     rect.render_widget(
-                        render_table("Today's Forecast", app_state.weather.clone()),
-                        table_chunks[1],
-                    )
+                        render_advice(&app_state.weather, &app_state.config.advice),
+                        horizontal_layout[1],
+                    );
+                }
+                // display the best upcoming windows for each activity
+                "Activity" => {
+                    render_activities(
+                        rect,
+                        horizontal_layout[1],
+                        &app_state.weather,
+                        &app_state.config.activities,
+                    );
                 }
-                &&_ => (),
+                // display details of active weather alerts
+                "Alerts" => {
+                    rect.render_widget(render_alerts(&app_state.weather), horizontal_layout[1]);
+                }
+                _ => (),
             }
 
             // list of available commands
             let footer = render_footer();
 
-            rect.render_widget(header, chunks[0]);
+            let header_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(48), Constraint::Min(10)].as_ref())
+                .split(chunks[0]);
+            rect.render_widget(header, header_chunks[0]);
+            if let Some(today) = app_state.weather.current_day() {
+                rect.render_widget(
+                    render_summary(&app_state.weather, today, app_state.config.language),
+                    header_chunks[1],
+                );
+            }
             rect.render_widget(input, chunks[1]);
             rect.render_widget(menu, horizontal_layout[0]);
-            rect.render_widget(footer, chunks[3]);
+            rect.render_widget(render_alert_banner(&app_state.weather), chunks[2]);
+            rect.render_widget(footer, chunks[4]);
         })?;
 
-        match process_keypress(&mut terminal_state.terminal, &mut app_state) {
-            Some(input) => match input {
-                Input::QUIT => break,
-                Input::SEARCH => {
+        if let Some(input) = process_keypress(&mut terminal_state.terminal, &mut app_state) {
+            match input {
+                Input::Quit => break,
+                Input::Search => {
                     app_state.toggle_search();
                 }
-                Input::DOWN => {
+                Input::Down => {
                     if selected_index < items.len() - 1 {
                         selected_index += 1;
                     }
                 }
-                Input::UP => {
-                    if selected_index > 0 {
-                        selected_index -= 1;
-                    }
+                Input::Up => {
+                    selected_index = selected_index.saturating_sub(1);
                 }
-                Input::CHAR => {
+                Input::Char => {
                     continue;
                 }
-                Input::ESCAPE => {
+                Input::Escape => {
                     app_state.toggle_search();
                 }
-                Input::REMOVE => {
+                Input::Remove => {
                     app_state.remove_last_char();
                 }
-                Input::ENTER => {
-                    app_state.weather = match get_current_weather(&app_state.input, None) {
-                        Some(data) => data,
-                        None => {
+                Input::Enter => {
+                    app_state.weather = match get_weather(&app_state.input, &app_state.query) {
+                        Ok(data) => data,
+                        Err(e) => {
                             let _ = restore(&mut terminal_state.terminal);
+                            eprintln!("{}", e);
                             return Ok(());
                         }
                     };
+                    let location = app_state.input.clone();
+                    app_state.record(&location);
                     app_state.toggle_search();
                 }
-            },
-            None => (),
+            }
         }
     }
 
@@ -223,6 +422,17 @@ fn render_header<'a>() -> Paragraph<'a> {
         .block(Block::default().borders(Borders::NONE))
 }
 
+fn render_summary<'a>(weather: &Weather, day: &Forecastday, language: Language) -> Paragraph<'a> {
+    Paragraph::new(summarize_day(day, language))
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(weather.location.name.clone())
+                .borders(Borders::ALL),
+        )
+}
+
 fn render_search_menu<'a>(placeholder: &'a str) -> Paragraph<'a> {
     Paragraph::new(placeholder).block(Block::default().borders(Borders::ALL).title("Search(↵)"))
 }
@@ -230,86 +440,857 @@ fn render_search_menu<'a>(placeholder: &'a str) -> Paragraph<'a> {
 fn render_menu<'a>(items: &'a Vec<&str>, selected_index: usize) -> List<'a> {
     let mut list_items: Vec<ListItem> = vec![];
 
-    let mut curr: usize = 0;
-    for &item in items {
+    for (curr, &item) in items.iter().enumerate() {
         if curr == selected_index {
             list_items.push(ListItem::new(item).style(Style::default().bg(Color::Gray)));
         } else {
             list_items.push(ListItem::new(item));
         }
-
-        curr += 1;
     }
 
     List::new(list_items).block(Block::default().title("Options(↓↑)").borders(Borders::ALL))
 }
 
-fn render_table<'a>(title: &'a str, data: Weather) -> Table<'a> {
-    // extract morning/afternoon/evening/night times from data to fill table
-    let morning = data.get_morning_data().unwrap();
-    let afternoon = data.get_afternoon_data().unwrap();
-    let evening = data.get_evening_data().unwrap();
-    let night = data.get_night_data().unwrap();
+fn render_table<'a>(
+    title: String,
+    weather: &Weather,
+    date: NaiveDate,
+    day_parts: &[DayPart],
+) -> Table<'a> {
+    let rows: Vec<Row> = summarize(weather, date, day_parts)
+        .into_iter()
+        .map(|summary| {
+            let kind = summary.condition.kind();
 
-    Table::new(vec![
-        Row::new(vec![
-            Cell::from("Morning").style(Style::default().fg(Color::White)),
-            Cell::from(format!("{}C / {}F", &morning.temp_c, &morning.temp_f))
+            Row::new(vec![
+                Cell::from(format!(
+                    "{} {}{}",
+                    summary.part.name,
+                    summary.part.label(),
+                    // not all of the part's hours were fetched
+                    match summary.partial {
+                        true => "*",
+                        false => "",
+                    }
+                ))
                 .style(Style::default().fg(Color::White)),
-            Cell::from(morning.condition.text.to_string()).style(Style::default().fg(Color::White)),
-        ])
-        .bottom_margin(1),
-        Row::new(vec![
-            Cell::from("Afternoon").style(Style::default().fg(Color::White)),
-            Cell::from(format!("{}C / {}F", &afternoon.temp_c, &afternoon.temp_f))
+                Cell::from(format!(
+                    "{}-{}C / {}-{}F",
+                    summary.min_temp_c, summary.max_temp_c, summary.min_temp_f, summary.max_temp_f
+                ))
+                .style(Style::default().fg(Color::White)),
+                Cell::from(format!("{} {}", kind.icon(), summary.condition.text))
+                    .style(Style::default().fg(condition_color(kind))),
+                Cell::from(format!("{}%", summary.max_chance_of_rain))
+                    .style(Style::default().fg(Color::White)),
+                Cell::from(format!("{} km/h", summary.max_gust_kph))
+                    .style(Style::default().fg(Color::White)),
+            ])
+            .bottom_margin(1)
+        })
+        .collect();
+
+    Table::new(rows)
+        .style(Style::default().fg(Color::White))
+        .header(
+            Row::new(vec!["Time", "Temperature", "Condition", "Rain", "Gusts"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .widths(
+            [
+                Constraint::Percentage(22),
+                Constraint::Percentage(26),
+                Constraint::Percentage(26),
+                Constraint::Percentage(10),
+                Constraint::Percentage(16),
+            ]
+            .as_ref(),
+        )
+}
+
+/// Number of upcoming hours listed next to the current conditions
+const NEXT_HOURS: usize = 6;
+
+fn render_next_hours<'a>(weather: &Weather) -> Table<'a> {
+    let rows: Vec<Row> = weather
+        .next_n_hours(NEXT_HOURS)
+        .map(|hour| {
+            let kind = hour.condition.kind();
+
+            Row::new(vec![
+                Cell::from(hour.time.format("%H:%M").to_string()),
+                Cell::from(format!("{}C", hour.temp_c)),
+                Cell::from(format!("{:.0}C", hour.derived().apparent_temp_c)),
+                Cell::from(format!("{}%", hour.chance_of_rain)),
+                Cell::from(kind.icon()).style(Style::default().fg(condition_color(kind))),
+            ])
+        })
+        .collect();
+
+    Table::new(rows)
+        .style(Style::default().fg(Color::White))
+        .header(
+            Row::new(vec!["Time", "Temp", "Feels", "Rain", ""])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(Block::default().title("Next Hours").borders(Borders::ALL))
+        .widths(
+            [
+                Constraint::Percentage(24),
+                Constraint::Percentage(20),
+                Constraint::Percentage(20),
+                Constraint::Percentage(20),
+                Constraint::Percentage(16),
+            ]
+            .as_ref(),
+        )
+}
+
+fn condition_color(kind: ConditionKind) -> Color {
+    match kind {
+        ConditionKind::Clear => Color::Yellow,
+        ConditionKind::PartlyCloudy => Color::LightYellow,
+        ConditionKind::Cloudy | ConditionKind::Overcast | ConditionKind::Fog => Color::Gray,
+        ConditionKind::Drizzle | ConditionKind::Rain => Color::LightBlue,
+        ConditionKind::HeavyRain => Color::Blue,
+        ConditionKind::Sleet | ConditionKind::Hail => Color::LightCyan,
+        ConditionKind::Snow | ConditionKind::HeavySnow => Color::White,
+        ConditionKind::Thunder => Color::Magenta,
+        ConditionKind::Unknown => Color::White,
+    }
+}
+
+/// Hours (indexed as in `temperature_points`) with rain, snow etc. and their temperature
+fn precipitation_points(days: &[&Forecastday]) -> Vec<(f64, f64)> {
+    days.iter()
+        .flat_map(|day| &day.hour)
+        .enumerate()
+        .filter(|(_, hour)| hour.condition.kind().is_precipitation())
+        .map(|(i, hour)| (i as f64, hour.temp_c))
+        .collect()
+}
+
+/// Hourly temperatures (C) across the given days, indexed by hours since the start of the first day
+fn temperature_points(days: &[&Forecastday]) -> Vec<(f64, f64)> {
+    days.iter()
+        .flat_map(|day| &day.hour)
+        .enumerate()
+        .map(|(i, hour)| (i as f64, hour.temp_c))
+        .collect()
+}
+
+fn render_temperature_chart<'a>(
+    points: &'a [(f64, f64)],
+    precipitation: &'a [(f64, f64)],
+    days: &[&Forecastday],
+) -> Chart<'a> {
+    let min = points
+        .iter()
+        .map(|&(_, temp)| temp)
+        .fold(f64::MAX, f64::min);
+    let max = points
+        .iter()
+        .map(|&(_, temp)| temp)
+        .fold(f64::MIN, f64::max);
+    let (min, max) = match points.is_empty() {
+        true => (0.0, 1.0),
+        false => ((min - 2.0).floor(), (max + 2.0).ceil()),
+    };
+
+    let first = days
+        .first()
+        .map(|day| day.date.to_string())
+        .unwrap_or_default();
+    let last = days
+        .last()
+        .map(|day| day.date.to_string())
+        .unwrap_or_default();
+
+    let dataset = Dataset::default()
+        .name("Temperature (C)")
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Yellow))
+        .data(points);
+    let wet_hours = Dataset::default()
+        .name("Precipitation")
+        .marker(symbols::Marker::Dot)
+        .graph_type(GraphType::Scatter)
+        .style(Style::default().fg(Color::LightBlue))
+        .data(precipitation);
+
+    Chart::new(vec![dataset, wet_hours])
+        .block(Block::default().title("Temperature").borders(Borders::ALL))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, points.len().saturating_sub(1).max(1) as f64])
+                .labels(vec![Span::from(first), Span::from(last)]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([min, max])
+                .labels(vec![
+                    Span::from(format!("{}", min)),
+                    Span::from(format!("{}", max)),
+                ]),
+        )
+}
+
+/// Hours of recorded observations plotted before the current conditions
+const RECORDED_HOURS: i64 = 48;
+/// Hours of forecast plotted after them
+const RECORDED_FORECAST_HOURS: i64 = 24;
+
+/// Recorded temperatures (C) against hours before the current conditions (negative)
+fn recorded_points(weather: &Weather, observations: &[Observation]) -> Vec<(f64, f64)> {
+    let Some(now) = weather
+        .current
+        .as_ref()
+        .map(|current| current.last_updated_epoch)
+    else {
+        return vec![];
+    };
+
+    observations
+        .iter()
+        .map(|observation| {
+            (
+                (observation.observed_at - now) as f64 / 3600.0,
+                observation.temp_c,
+            )
+        })
+        .filter(|&(hours, _)| hours >= -RECORDED_HOURS as f64)
+        .collect()
+}
+
+/// Forecast temperatures (C) against hours after the current conditions
+fn upcoming_points(weather: &Weather) -> Vec<(f64, f64)> {
+    let Some(now) = weather
+        .current
+        .as_ref()
+        .map(|current| current.last_updated_epoch)
+    else {
+        return vec![];
+    };
+
+    weather
+        .hours()
+        .map(|hour| ((hour.time_epoch - now) as f64 / 3600.0, hour.temp_c))
+        .filter(|&(hours, _)| (0.0..=RECORDED_FORECAST_HOURS as f64).contains(&hours))
+        .collect()
+}
+
+fn render_recorded_chart<'a>(recorded: &'a [(f64, f64)], forecast: &'a [(f64, f64)]) -> Chart<'a> {
+    let temperatures = recorded.iter().chain(forecast).map(|&(_, temp)| temp);
+    let min = temperatures.clone().fold(f64::MAX, f64::min);
+    let max = temperatures.fold(f64::MIN, f64::max);
+    let (min, max) = match recorded.is_empty() && forecast.is_empty() {
+        true => (0.0, 1.0),
+        false => ((min - 2.0).floor(), (max + 2.0).ceil()),
+    };
+
+    let title = match recorded.is_empty() {
+        true => format!("Recorded (nothing in the last {} hours)", RECORDED_HOURS),
+        false => String::from("Recorded"),
+    };
+
+    let past = Dataset::default()
+        .name("Recorded (C)")
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Green))
+        .data(recorded);
+    let ahead = Dataset::default()
+        .name("Forecast (C)")
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Yellow))
+        .data(forecast);
+
+    Chart::new(vec![past, ahead])
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([-RECORDED_HOURS as f64, RECORDED_FORECAST_HOURS as f64])
+                .labels(vec![
+                    Span::from(format!("-{}h", RECORDED_HOURS)),
+                    Span::from("now"),
+                    Span::from(format!("+{}h", RECORDED_FORECAST_HOURS)),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([min, max])
+                .labels(vec![
+                    Span::from(format!("{}", min)),
+                    Span::from(format!("{}", max)),
+                ]),
+        )
+}
+
+/// Number of points plotted between each high and low tide
+const TIDE_CURVE_STEPS: usize = 24;
+
+/// Tide height (m) against hours since the start of the first day
+/// Heights between the reported highs and lows follow a half cosine, which is a close fit for
+/// semi-diurnal tides
+fn tide_points(days: &[&Forecastday]) -> Vec<(f64, f64)> {
+    let Some(start) = days.first().and_then(|day| day.date.and_hms_opt(0, 0, 0)) else {
+        return vec![];
+    };
+
+    let extremes: Vec<(f64, f64)> = days
+        .iter()
+        .flat_map(|day| day.tides())
+        .map(|tide| {
+            let hours = (tide.tide_time - start).num_minutes() as f64 / 60.0;
+            (hours, tide.tide_height_mt)
+        })
+        .collect();
+
+    let mut points = vec![];
+    for pair in extremes.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        for step in 0..TIDE_CURVE_STEPS {
+            let t = step as f64 / TIDE_CURVE_STEPS as f64;
+            let height = y0 + (y1 - y0) * (1.0 - (t * std::f64::consts::PI).cos()) / 2.0;
+            points.push((x0 + (x1 - x0) * t, height));
+        }
+    }
+    points.extend(extremes.last());
+
+    points
+}
+
+fn render_tide_chart<'a>(points: &'a [(f64, f64)], days: &[&Forecastday]) -> Chart<'a> {
+    let min = points
+        .iter()
+        .map(|&(_, height)| height)
+        .fold(f64::MAX, f64::min);
+    let max = points
+        .iter()
+        .map(|&(_, height)| height)
+        .fold(f64::MIN, f64::max);
+    let (min, max) = match points.is_empty() {
+        true => (0.0, 1.0),
+        false => ((min * 2.0).floor() / 2.0, (max * 2.0).ceil() / 2.0),
+    };
+
+    let first = days
+        .first()
+        .map(|day| day.date.to_string())
+        .unwrap_or_default();
+    let last = days
+        .last()
+        .map(|day| day.date.to_string())
+        .unwrap_or_default();
+
+    let dataset = Dataset::default()
+        .name("Tide height (m)")
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Cyan))
+        .data(points);
+
+    Chart::new(vec![dataset])
+        .block(Block::default().title("Tides").borders(Borders::ALL))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, (days.len().max(1) * 24) as f64])
+                .labels(vec![Span::from(first), Span::from(last)]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([min, max])
+                .labels(vec![
+                    Span::from(format!("{}m", min)),
+                    Span::from(format!("{}m", max)),
+                ]),
+        )
+}
+
+fn render_marine_table<'a>(day: &Forecastday) -> Table<'a> {
+    // every third hour keeps the table to a single screen
+    let rows: Vec<Row> = day
+        .hour
+        .iter()
+        .step_by(3)
+        .map(|hour| {
+            Row::new(vec![
+                hour.time.format("%H:%M").to_string(),
+                format_marine(hour.sig_ht_mt, "m"),
+                format!(
+                    "{} {}",
+                    format_marine(hour.swell_ht_mt, "m"),
+                    hour.swell_dir_16_point.clone().unwrap_or_default()
+                ),
+                format_marine(hour.swell_period_secs, "s"),
+                format_marine(hour.water_temp_c, "C"),
+            ])
+        })
+        .collect();
+
+    Table::new(rows)
+        .style(Style::default().fg(Color::White))
+        .header(
+            Row::new(vec!["Time", "Waves", "Swell", "Period", "Water"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(
+            Block::default()
+                .title("Sea Conditions")
+                .borders(Borders::ALL),
+        )
+        .widths(
+            [
+                Constraint::Percentage(16),
+                Constraint::Percentage(18),
+                Constraint::Percentage(30),
+                Constraint::Percentage(16),
+                Constraint::Percentage(20),
+            ]
+            .as_ref(),
+        )
+}
+
+fn format_marine(value: Option<f64>, unit: &str) -> String {
+    value.map_or(String::from("-"), |value| format!("{}{}", value, unit))
+}
+
+fn render_tide_times<'a>(day: &Forecastday) -> Paragraph<'a> {
+    let mut lines: Vec<String> = day
+        .tides()
+        .map(|tide| {
+            format!(
+                "{} {} ({}m)",
+                tide.tide_time.format("%H:%M"),
+                tide.tide_type,
+                tide.tide_height_mt
+            )
+        })
+        .collect();
+    // Open-Meteo's marine forecast has no tides
+    if lines.is_empty() {
+        lines.push(String::from("No tide times"));
+    }
+
+    Paragraph::new(lines.join("\n"))
+        .style(Style::default().fg(Color::White))
+        .block(
+            Block::default()
+                .title("Today's Tides")
+                .borders(Borders::ALL)
                 .style(Style::default().fg(Color::White)),
-            Cell::from(afternoon.condition.text.to_string())
+        )
+}
+
+fn render_forecast<'a>(weather: &Weather) -> Paragraph<'a> {
+    let mut lines = vec![weather.to_string()];
+    if let Some(current) = &weather.current {
+        let derived = current.derived();
+        lines.extend([
+            String::new(),
+            format!(
+                "Apparent: {:.1}C ({})",
+                derived.apparent_temp_c, derived.thermal_stress
+            ),
+            format!("Wet bulb: {:.1}C", derived.wet_bulb_c),
+            format!(
+                "Humidity: {}% ({:.1} g/m3)",
+                current.humidity, derived.absolute_humidity
+            ),
+            format!("Cloud base: ~{:.0} m", derived.cloud_base_m),
+            format!("Wind: {} {}", derived.beaufort, current.wind_dir),
+        ]);
+        if let Some((change, tendency)) = weather.pressure_tendency() {
+            lines.push(format!(
+                "Pressure: {} mb, {} ({:+.1} in 3h)",
+                current.pressure_mb, tendency, change
+            ));
+        }
+    }
+
+    Paragraph::new(lines.join("\n"))
+        .style(Style::default().fg(Color::White))
+        .block(
+            Block::default()
+                .title("Current Conditions")
+                .borders(Borders::ALL)
                 .style(Style::default().fg(Color::White)),
-        ])
-        .bottom_margin(1),
-        Row::new(vec![
-            Cell::from("Evening").style(Style::default().fg(Color::White)),
-            Cell::from(format!("{}C / {}F", &evening.temp_c, &evening.temp_f))
+        )
+}
+
+fn render_astronomy<'a>(weather: &Weather) -> Paragraph<'a> {
+    let mut lines: Vec<String> = vec![];
+
+    if let Some(today) = weather.current_day() {
+        let astro = &today.astro;
+
+        lines.push(format!("Sunrise: {}", format_astro_time(astro.sunrise)));
+        lines.push(format!("Sunset: {}", format_astro_time(astro.sunset)));
+        if let Some(daylight) = astro.daylight() {
+            lines.push(format!(
+                "Daylight: {}h {}m",
+                daylight.num_hours(),
+                daylight.num_minutes() % 60
+            ));
+        }
+        lines.push(String::new());
+        lines.push(format!("Moonrise: {}", format_astro_time(astro.moonrise)));
+        lines.push(format!("Moonset: {}", format_astro_time(astro.moonset)));
+        lines.push(format!(
+            "Moon: {} {} ({}% illuminated)",
+            astro.moon_phase_glyph(),
+            astro.moon_phase,
+            astro.moon_illumination
+        ));
+
+        // golden/blue hour aren't provided by the API, so compute them from the coordinates
+        if let Some(solar_day) = weather.location.solar_day(today.date) {
+            lines.push(String::new());
+            lines.push(format!(
+                "Solar noon: {}",
+                solar_day.solar_noon().format("%H:%M")
+            ));
+            for (name, twilight) in [
+                ("Civil", Twilight::Civil),
+                ("Nautical", Twilight::Nautical),
+                ("Astronomical", Twilight::Astronomical),
+            ] {
+                let dawn = solar_day.dawn(twilight).map(|time| time.format("%H:%M"));
+                let dusk = solar_day.dusk(twilight).map(|time| time.format("%H:%M"));
+                match (dawn, dusk) {
+                    (Some(dawn), Some(dusk)) => {
+                        lines.push(format!("{} twilight: {} / {}", name, dawn, dusk))
+                    }
+                    _ => lines.push(format!("{} twilight: none", name)),
+                }
+            }
+            if let Some((morning, evening)) = solar_day.golden_hour() {
+                lines.push(format!(
+                    "Golden hour: {}, {}",
+                    morning.format("%H:%M"),
+                    evening.format("%H:%M")
+                ));
+            }
+            if let Some((morning, evening)) = solar_day.blue_hour() {
+                lines.push(format!(
+                    "Blue hour: {}, {}",
+                    morning.format("%H:%M"),
+                    evening.format("%H:%M")
+                ));
+            }
+        }
+    }
+
+    Paragraph::new(lines.join("\n"))
+        .style(Style::default().fg(Color::White))
+        .block(
+            Block::default()
+                .title("Astronomy")
+                .borders(Borders::ALL)
                 .style(Style::default().fg(Color::White)),
-            Cell::from(evening.condition.text.to_string()).style(Style::default().fg(Color::White)),
-        ])
-        .bottom_margin(1),
+        )
+}
+
+/// Astro times are shown as the API gives them, e.g. 06:42 AM
+fn format_astro_time(time: Option<NaiveTime>) -> String {
+    time.map_or(String::from("None today"), |time| {
+        time.format("%I:%M %p").to_string()
+    })
+}
+
+/// Progress bar showing how far the sun is between today's sunrise and sunset
+fn render_sun_progress<'a>(weather: &Weather) -> Gauge<'a> {
+    let now = Some(weather.location.localtime.time());
+    let astro = weather.current_day().map(|today| &today.astro);
+
+    let (ratio, label) = match (
+        now,
+        astro.and_then(|astro| astro.sunrise),
+        astro.and_then(|astro| astro.sunset),
+    ) {
+        (Some(now), Some(sunrise), Some(sunset)) if sunset > sunrise => {
+            if now < sunrise {
+                (0.0, String::from("Before sunrise"))
+            } else if now > sunset {
+                (1.0, String::from("After sunset"))
+            } else {
+                let ratio =
+                    (now - sunrise).num_seconds() as f64 / (sunset - sunrise).num_seconds() as f64;
+                (ratio, format!("{:.0}% of daylight elapsed", ratio * 100.0))
+            }
+        }
+        _ => (0.0, String::from("Unavailable")),
+    };
+
+    Gauge::default()
+        .block(Block::default().title("Sun Position").borders(Borders::ALL))
+        .gauge_style(Style::default().fg(Color::Yellow))
+        .ratio(ratio)
+        .label(label)
+}
+
+fn render_air_quality<'a>(weather: &Weather) -> Table<'a> {
+    let block = Block::default().title("Air Quality").borders(Borders::ALL);
+    let white = Style::default().fg(Color::White);
+
+    let air_quality = match weather
+        .current
+        .as_ref()
+        .and_then(|current| current.air_quality.as_ref())
+    {
+        Some(air_quality) => air_quality,
+        None => {
+            return Table::new(vec![Row::new(vec!["Air quality data unavailable"])])
+                .style(white)
+                .block(block)
+                .widths([Constraint::Percentage(100)].as_ref());
+        }
+    };
+
+    let epa = air_quality.epa_category();
+    let defra = air_quality.defra_band();
+
+    let mut rows = vec![
         Row::new(vec![
-            Cell::from("Night").style(Style::default().fg(Color::White)),
-            Cell::from(format!("{}C / {}F", &night.temp_c, &night.temp_f))
-                .style(Style::default().fg(Color::White)),
-            Cell::from(night.condition.text.to_string()).style(Style::default().fg(Color::White)),
+            Cell::from("US EPA index"),
+            Cell::from(air_quality.us_epa_index.to_string()),
+            Cell::from(epa.map(|category| category.to_string()).unwrap_or_default())
+                .style(Style::default().fg(epa.map_or(Color::White, epa_color))),
         ]),
-    ])
-    .style(Style::default().fg(Color::White))
-    .header(
-        Row::new(vec!["Time", "Temperature", "Condition"])
-            .style(Style::default().fg(Color::Yellow)),
-    )
-    .block(Block::default().title(title).borders(Borders::ALL))
-    .widths(
-        [
-            Constraint::Percentage(33),
-            Constraint::Percentage(34),
-            Constraint::Percentage(33),
-        ]
-        .as_ref(),
-    )
-}
-
-fn render_forecast<'a>(data: &'a String) -> Paragraph<'a> {
-    Paragraph::new(data.to_string())
+        Row::new(vec![
+            Cell::from("UK DEFRA index"),
+            Cell::from(air_quality.gb_defra_index.to_string()),
+            Cell::from(defra.map(|band| band.to_string()).unwrap_or_default())
+                .style(Style::default().fg(defra.map_or(Color::White, defra_color))),
+        ])
+        .bottom_margin(1),
+    ];
+
+    for (name, value) in [
+        ("Carbon monoxide (CO)", air_quality.co),
+        ("Nitrogen dioxide (NO2)", air_quality.no2),
+        ("Ozone (O3)", air_quality.o3),
+        ("Sulphur dioxide (SO2)", air_quality.so2),
+        ("PM2.5", air_quality.pm2_5),
+        ("PM10", air_quality.pm10),
+    ] {
+        rows.push(Row::new(vec![
+            Cell::from(name),
+            Cell::from(format!("{:.1} μg/m3", value)),
+            Cell::from(""),
+        ]));
+    }
+
+    Table::new(rows)
+        .style(white)
+        .header(
+            Row::new(vec!["Measure", "Value", "Category"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(block)
+        .widths(
+            [
+                Constraint::Percentage(40),
+                Constraint::Percentage(25),
+                Constraint::Percentage(35),
+            ]
+            .as_ref(),
+        )
+}
+
+/// Standard colors of the US EPA air quality categories
+fn epa_color(category: EpaCategory) -> Color {
+    match category {
+        EpaCategory::Good => Color::Green,
+        EpaCategory::Moderate => Color::Yellow,
+        EpaCategory::UnhealthyForSensitiveGroups => Color::LightRed,
+        EpaCategory::Unhealthy => Color::Red,
+        EpaCategory::VeryUnhealthy => Color::Magenta,
+        EpaCategory::Hazardous => Color::Rgb(126, 0, 35),
+    }
+}
+
+fn defra_color(band: DefraBand) -> Color {
+    match band {
+        DefraBand::Low => Color::Green,
+        DefraBand::Moderate => Color::Yellow,
+        DefraBand::High => Color::Red,
+        DefraBand::VeryHigh => Color::Magenta,
+    }
+}
+
+fn render_alert_banner<'a>(weather: &Weather) -> Paragraph<'a> {
+    let alerts = &weather.alerts.alert;
+    let color = severity_color(weather.alerts.max_severity().unwrap_or_default());
+
+    let text = match alerts.first() {
+        Some(alert) if alerts.len() == 1 => format!("⚠ {}", alert),
+        Some(alert) => format!("⚠ {} (+{} more)", alert, alerts.len() - 1),
+        None => String::new(),
+    };
+
+    Paragraph::new(text)
+        .style(Style::default().fg(Color::Black).bg(color))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Weather Alert"),
+        )
+}
+
+fn format_alert_time(time: Option<DateTime<FixedOffset>>) -> String {
+    time.map_or(String::from("-"), |time| {
+        time.format("%Y-%m-%d %H:%M").to_string()
+    })
+}
+
+fn render_advice<'a>(weather: &Weather, config: &AdviceConfig) -> Paragraph<'a> {
+    let mut lines: Vec<Spans> = vec![];
+
+    for advice in advise(weather, config) {
+        lines.push(Spans::from(Span::styled(
+            format!(
+                "{} {} ({})",
+                advice.date.format("%a %d %b"),
+                advice.window.name,
+                advice.window.label()
+            ),
+            Style::default().fg(Color::Yellow),
+        )));
+
+        if advice.recommendations.is_empty() {
+            lines.push(Spans::from("  Nothing extra needed"));
+        }
+        for (recommendation, reason) in advice.recommendations {
+            lines.push(Spans::from(format!(
+                "  {} {}: {}",
+                recommendation.icon(),
+                recommendation,
+                reason
+            )));
+        }
+        lines.push(Spans::from(""));
+    }
+
+    if lines.is_empty() {
+        lines.push(Spans::from(
+            "No forecast for the advice windows, try a longer --forecast",
+        ));
+    }
+
+    Paragraph::new(lines)
         .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().title("Advice").borders(Borders::ALL))
+}
+
+/// Number of windows shown per activity
+const ACTIVITY_WINDOWS: usize = 3;
+
+/// Best windows for each activity, with a bar showing how well each one fits the profile
+fn render_activities(
+    rect: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    weather: &Weather,
+    profiles: &[ActivityProfile],
+) {
+    let block = Block::default().title("Activity").borders(Borders::ALL);
+    let inner = block.inner(area);
+    rect.render_widget(block, area);
+
+    let gauges: Vec<Gauge> = profiles
+        .iter()
+        .flat_map(|profile| {
+            best_windows(weather, profile, ACTIVITY_WINDOWS)
+                .into_iter()
+                .map(move |window| {
+                    Gauge::default()
+                        .block(Block::default().title(format!(
+                            "{}: {} - {}",
+                            profile.name,
+                            window.start.format("%a %H:%M"),
+                            window.end.format("%H:%M")
+                        )))
+                        .gauge_style(Style::default().fg(score_color(window.score)))
+                        .percent(window.score as u16)
+                })
+        })
+        .collect();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(2); gauges.len()])
+        .split(inner);
+    for (gauge, chunk) in gauges.into_iter().zip(chunks) {
+        rect.render_widget(gauge, chunk);
+    }
+}
+
+fn score_color(score: u8) -> Color {
+    match score {
+        80..=100 => Color::Green,
+        50..=79 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+fn render_alerts<'a>(weather: &Weather) -> Paragraph<'a> {
+    let mut lines: Vec<Spans> = vec![];
+
+    for alert in &weather.alerts.alert {
+        lines.push(Spans::from(Span::styled(
+            alert.event.clone(),
+            Style::default().fg(severity_color(alert.severity)),
+        )));
+        lines.push(Spans::from(format!(
+            "Severity: {:?}  Urgency: {:?}",
+            alert.severity, alert.urgency
+        )));
+        lines.push(Spans::from(format!("Areas: {}", alert.areas)));
+        lines.push(Spans::from(format!(
+            "Effective: {}  Expires: {}",
+            format_alert_time(alert.effective),
+            format_alert_time(alert.expires)
+        )));
+        lines.push(Spans::from(alert.desc.clone()));
+        lines.push(Spans::from(""));
+    }
+
+    if lines.is_empty() {
+        lines.push(Spans::from("No active alerts"));
+    }
+
+    Paragraph::new(lines)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true })
         .block(
             Block::default()
-                .title("Current Conditions")
+                .title("Alerts")
                 .borders(Borders::ALL)
                 .style(Style::default().fg(Color::White)),
         )
 }
 
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Extreme => Color::Magenta,
+        Severity::Severe => Color::Red,
+        Severity::Moderate => Color::LightRed,
+        Severity::Minor | Severity::Unknown => Color::Yellow,
+    }
+}
+
 fn render_footer<'a>() -> Paragraph<'a> {
-    Paragraph::new("Press 'q': QUIT program")
+    Paragraph::new("Press 'q': Quit program")
         .style(Style::default().fg(Color::LightGreen))
         .alignment(Alignment::Left)
         .block(
@@ -334,38 +1315,38 @@ fn process_keypress(
         }) => {
             if app_state.is_search_active {
                 app_state.update_input(c);
-                return Some(Input::CHAR);
+                return Some(Input::Char);
             }
             match c {
                 'q' => {
                     let _ = restore(terminal);
-                    Some(Input::QUIT)
+                    Some(Input::Quit)
                 }
                 '/' => {
                     // app_state.toggle_search();
-                    Some(Input::SEARCH)
+                    Some(Input::Search)
                 }
                 _ => None,
             }
         }
         Event::Key(KeyEvent {
             code: KeyCode::Esc, ..
-        }) => Some(Input::ESCAPE),
+        }) => Some(Input::Escape),
         Event::Key(KeyEvent {
             code: KeyCode::Down,
             ..
-        }) => Some(Input::DOWN),
+        }) => Some(Input::Down),
         Event::Key(KeyEvent {
             code: KeyCode::Up, ..
-        }) => Some(Input::UP),
+        }) => Some(Input::Up),
         Event::Key(KeyEvent {
             code: KeyCode::Backspace,
             ..
-        }) => Some(Input::REMOVE),
+        }) => Some(Input::Remove),
         Event::Key(KeyEvent {
             code: KeyCode::Enter,
             ..
-        }) => Some(Input::ENTER),
+        }) => Some(Input::Enter),
         _ => None,
     }
 }
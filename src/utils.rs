@@ -17,3 +17,31 @@ pub fn read_file(path: &str) -> String {
 
     result
 }
+
+/// Lowercase alphanumeric words joined by underscores, for topics and ids
+/// e.g. "New York, US" becomes new_york_us. Other letters are written as the hex of their
+/// UTF-8 bytes, so "北京" becomes e58c97e4baac and non-Latin names stay distinct
+pub fn slug(text: &str) -> String {
+    let mut words = vec![];
+    let mut word = String::new();
+    for c in text.to_lowercase().chars() {
+        match (c.is_ascii_alphanumeric(), c.is_alphanumeric()) {
+            (true, _) => word.push(c),
+            (false, true) => {
+                for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                    word.push_str(&format!("{:02x}", byte));
+                }
+            }
+            (false, false) => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words.join("_")
+}
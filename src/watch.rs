@@ -0,0 +1,737 @@
+use std::{
+    collections::{HashMap, HashSet},
+    thread,
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::{
+    api::{get_weather, Query},
+    day_parts::DayPart,
+    notify::{Notification, Notifier, NotifyError, SinkConfig},
+    output::render_template,
+    recorder::{record, Recorder},
+    summary::{summarize_day, Language},
+    weather::{Day, Hour, Severity, Weather},
+};
+
+/// Sink that sends desktop notifications, available without being configured
+const DESKTOP_SINK: &str = "desktop";
+
+/// Days fetched each check, enough for rules about tonight or tomorrow. More are fetched
+/// when a daily rule looks further ahead
+const WATCH_FORECAST_DAYS: u8 = 2;
+/// Most days weatherapi.com forecasts, and so the furthest a daily rule can look
+const MAX_FORECAST_DAYS: u8 = 14;
+
+/// Locations checked by the `watch` subcommand, how often, and the rules that trigger notifications
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Checked instead of --location when set
+    pub locations: Vec<String>,
+    pub interval_minutes: u64,
+    pub rules: Vec<Rule>,
+    /// Where notifications can be sent, besides the built-in `desktop` sink
+    pub sinks: Vec<SinkConfig>,
+}
+
+/// Trigger with the sinks its notifications go to
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Rule {
+    #[serde(flatten)]
+    pub trigger: Trigger,
+    /// Names of the sinks to notify, the desktop when empty
+    #[serde(default)]
+    pub sinks: Vec<String>,
+    /// Message body with `{field}` placeholders filled in from the weather, like --template.
+    /// `{summary}` and `{body}` are the rule's own message
+    pub template: Option<String>,
+}
+
+/// Condition that sends a notification when it starts to hold
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Any hour in the next `hours`, or in the next occurrence of `day_part`, crossing a threshold
+    Hourly {
+        field: HourField,
+        above: Option<f64>,
+        below: Option<f64>,
+        #[serde(default = "default_hours")]
+        hours: u32,
+        day_part: Option<String>,
+    },
+    /// Any of the next `days` days crossing a threshold
+    Daily {
+        field: DayField,
+        above: Option<f64>,
+        below: Option<f64>,
+        #[serde(default = "default_days")]
+        days: usize,
+    },
+    /// Any weather alert being issued
+    Alert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HourField {
+    TempC,
+    FeelslikeC,
+    WindchillC,
+    ChanceOfRain,
+    ChanceOfSnow,
+    PrecipMm,
+    WindKph,
+    GustKph,
+    Uv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DayField {
+    MaxtempC,
+    MintempC,
+    DailyChanceOfRain,
+    DailyChanceOfSnow,
+    TotalprecipMm,
+    TotalsnowCm,
+    MaxwindKph,
+    Uv,
+}
+
+/// Notification for a rule that holds, `key` identifies it between checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Firing {
+    pub key: String,
+    /// Index of the rule in the config
+    pub rule: usize,
+    pub notification: Notification,
+}
+
+/// Remembers which rules held at the last check of each location, so each only notifies once
+/// until it stops holding
+#[derive(Debug, Default)]
+pub struct Watcher {
+    active: HashMap<String, HashSet<String>>,
+}
+
+fn default_hours() -> u32 {
+    3
+}
+
+fn default_days() -> usize {
+    1
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            locations: vec![],
+            interval_minutes: 30,
+            rules: vec![
+                Rule::new(Trigger::Hourly {
+                    field: HourField::ChanceOfRain,
+                    above: Some(60.0),
+                    below: None,
+                    hours: default_hours(),
+                    day_part: None,
+                }),
+                Rule::new(Trigger::Hourly {
+                    field: HourField::TempC,
+                    above: None,
+                    below: Some(0.0),
+                    hours: default_hours(),
+                    day_part: Some(String::from("Night")),
+                }),
+                Rule::new(Trigger::Alert),
+            ],
+            sinks: vec![],
+        }
+    }
+}
+
+impl WatchConfig {
+    /// Checks each rule, `day_parts` are the names an hourly rule can refer to
+    pub fn validate(&self, day_parts: &[DayPart]) -> Result<(), String> {
+        // checking without a pause would call the API as fast as it answers
+        if self.interval_minutes < 1 {
+            return Err(String::from("watch interval_minutes must be at least 1"));
+        }
+
+        for rule in &self.rules {
+            rule.trigger.validate(day_parts)?;
+
+            for name in &rule.sinks {
+                if name != DESKTOP_SINK && !self.sinks.iter().any(|sink| &sink.name == name) {
+                    return Err(format!("rule refers to unknown sink '{}'", name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Days fetched each check, enough for the daily rule that looks furthest ahead
+    fn forecast_days(&self) -> u8 {
+        self.rules
+            .iter()
+            .filter_map(|rule| match rule.trigger {
+                Trigger::Daily { days, .. } => Some(days.min(MAX_FORECAST_DAYS as usize) as u8),
+                _ => None,
+            })
+            .fold(WATCH_FORECAST_DAYS, u8::max)
+    }
+
+    /// Connects to the sinks used by the rules, by name
+    pub fn build_sinks(&self) -> Result<HashMap<String, Box<dyn Notifier>>, NotifyError> {
+        let mut sinks = HashMap::new();
+
+        for name in self.rules.iter().flat_map(|rule| rule.sink_names()) {
+            if sinks.contains_key(name) {
+                continue;
+            }
+
+            let config = match self.sinks.iter().find(|sink| sink.name == name) {
+                Some(config) => config.clone(),
+                None => SinkConfig::desktop(),
+            };
+            sinks.insert(name.to_string(), config.build()?);
+        }
+
+        Ok(sinks)
+    }
+}
+
+impl Rule {
+    pub fn new(trigger: Trigger) -> Rule {
+        Rule {
+            trigger,
+            sinks: vec![],
+            template: None,
+        }
+    }
+
+    fn sink_names(&self) -> Vec<&str> {
+        match self.sinks.is_empty() {
+            true => vec![DESKTOP_SINK],
+            false => self.sinks.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl HourField {
+    fn value(&self, hour: &Hour) -> f64 {
+        match self {
+            HourField::TempC => hour.temp_c,
+            HourField::FeelslikeC => hour.feelslike_c,
+            HourField::WindchillC => hour.windchill_c,
+            HourField::ChanceOfRain => hour.chance_of_rain as f64,
+            HourField::ChanceOfSnow => hour.chance_of_snow as f64,
+            HourField::PrecipMm => hour.precip_mm,
+            HourField::WindKph => hour.wind_kph,
+            HourField::GustKph => hour.gust_kph,
+            HourField::Uv => hour.uv,
+        }
+    }
+
+    fn name(&self) -> (&'static str, &'static str) {
+        match self {
+            HourField::TempC => ("Temperature", "C"),
+            HourField::FeelslikeC => ("Feels like", "C"),
+            HourField::WindchillC => ("Wind chill", "C"),
+            HourField::ChanceOfRain => ("Chance of rain", "%"),
+            HourField::ChanceOfSnow => ("Chance of snow", "%"),
+            HourField::PrecipMm => ("Precipitation", " mm"),
+            HourField::WindKph => ("Wind", " km/h"),
+            HourField::GustKph => ("Gusts", " km/h"),
+            HourField::Uv => ("UV index", ""),
+        }
+    }
+}
+
+impl DayField {
+    fn value(&self, day: &Day) -> f64 {
+        match self {
+            DayField::MaxtempC => day.maxtemp_c,
+            DayField::MintempC => day.mintemp_c,
+            DayField::DailyChanceOfRain => day.daily_chance_of_rain as f64,
+            DayField::DailyChanceOfSnow => day.daily_chance_of_snow as f64,
+            DayField::TotalprecipMm => day.totalprecip_mm,
+            DayField::TotalsnowCm => day.totalsnow_cm,
+            DayField::MaxwindKph => day.maxwind_kph,
+            DayField::Uv => day.uv,
+        }
+    }
+
+    fn name(&self) -> (&'static str, &'static str) {
+        match self {
+            DayField::MaxtempC => ("High", "C"),
+            DayField::MintempC => ("Low", "C"),
+            DayField::DailyChanceOfRain => ("Chance of rain", "%"),
+            DayField::DailyChanceOfSnow => ("Chance of snow", "%"),
+            DayField::TotalprecipMm => ("Precipitation", " mm"),
+            DayField::TotalsnowCm => ("Snowfall", " cm"),
+            DayField::MaxwindKph => ("Wind", " km/h"),
+            DayField::Uv => ("UV index", ""),
+        }
+    }
+}
+
+impl Trigger {
+    /// Checks the trigger's settings, `day_parts` are the names an hourly trigger can refer to
+    fn validate(&self, day_parts: &[DayPart]) -> Result<(), String> {
+        match self {
+            Trigger::Hourly {
+                above,
+                below,
+                day_part,
+                ..
+            } => {
+                if above.is_none() && below.is_none() {
+                    return Err(String::from("hourly rules need 'above' or 'below'"));
+                }
+                if let Some(name) = day_part {
+                    if find_day_part(day_parts, name).is_none() {
+                        return Err(format!("rule refers to unknown day part '{}'", name));
+                    }
+                }
+            }
+            Trigger::Daily {
+                above, below, days, ..
+            } => {
+                if above.is_none() && below.is_none() {
+                    return Err(String::from("daily rules need 'above' or 'below'"));
+                }
+                if !(1..=MAX_FORECAST_DAYS as usize).contains(days) {
+                    return Err(format!(
+                        "daily rules need 'days' between 1 and {}",
+                        MAX_FORECAST_DAYS
+                    ));
+                }
+            }
+            Trigger::Alert => (),
+        }
+
+        Ok(())
+    }
+}
+
+fn find_day_part<'a>(day_parts: &'a [DayPart], name: &str) -> Option<&'a DayPart> {
+    day_parts
+        .iter()
+        .find(|part| part.name.eq_ignore_ascii_case(name))
+}
+
+fn crosses(value: f64, above: Option<f64>, below: Option<f64>) -> bool {
+    above.is_some_and(|above| value > above) || below.is_some_and(|below| value < below)
+}
+
+fn threshold(above: Option<f64>, below: Option<f64>, unit: &str) -> String {
+    match (above, below) {
+        (Some(above), Some(below)) => format!("above {}{} or below {}{}", above, unit, below, unit),
+        (Some(above), None) => format!("above {}{}", above, unit),
+        (_, Some(below)) => format!("below {}{}", below, unit),
+        (None, None) => String::new(),
+    }
+}
+
+/// Notification body: what triggered the rule, then the summary of the day it concerns
+fn with_summary(weather: &Weather, date: NaiveDate, text: String, language: Language) -> String {
+    match weather
+        .forecast
+        .forecastday
+        .iter()
+        .find(|day| day.date == date)
+    {
+        Some(day) => format!("{}\n{}", text, summarize_day(day, language)),
+        None => text,
+    }
+}
+
+/// Rules that hold for the weather, with a notification for each. Bodies end with the day's
+/// summary in `language`
+pub fn evaluate(
+    weather: &Weather,
+    rules: &[Rule],
+    day_parts: &[DayPart],
+    language: Language,
+) -> Vec<Firing> {
+    let location = &weather.location.name;
+    let now = weather.location.localtime;
+    let mut firings = vec![];
+
+    for (i, rule) in rules.iter().enumerate() {
+        let mut notifications: Vec<(String, Notification)> = vec![];
+
+        match &rule.trigger {
+            Trigger::Hourly {
+                field,
+                above,
+                below,
+                hours,
+                day_part,
+            } => {
+                let (mut candidates, period): (Vec<&Hour>, String) = match day_part
+                    .as_deref()
+                    .and_then(|name| find_day_part(day_parts, name))
+                {
+                    Some(part) => {
+                        let (start, end) = part.next_range(now);
+                        (
+                            weather.hours_between(start.max(now), end).collect(),
+                            format!("this {}", part.name.to_lowercase()),
+                        )
+                    }
+                    None => (
+                        weather.next_n_hours(*hours as usize).collect(),
+                        format!("in the next {} hours", hours),
+                    ),
+                };
+                candidates.retain(|hour| crosses(field.value(hour), *above, *below));
+
+                if let Some(hour) = candidates.first() {
+                    let (name, unit) = field.name();
+                    notifications.push((
+                        format!("rule-{}", i),
+                        Notification {
+                            summary: format!(
+                                "{}: {} {} {}",
+                                location,
+                                name,
+                                threshold(*above, *below, unit),
+                                period
+                            ),
+                            body: with_summary(
+                                weather,
+                                hour.time.date(),
+                                format!(
+                                    "{} {}{} at {}",
+                                    name,
+                                    field.value(hour),
+                                    unit,
+                                    hour.time.format("%a %H:%M")
+                                ),
+                                language,
+                            ),
+                            urgent: false,
+                        },
+                    ));
+                }
+            }
+            Trigger::Daily {
+                field,
+                above,
+                below,
+                days,
+            } => {
+                let day = weather
+                    .forecast
+                    .forecastday
+                    .iter()
+                    .take(*days)
+                    .find(|day| crosses(field.value(&day.day), *above, *below));
+
+                if let Some(day) = day {
+                    let (name, unit) = field.name();
+                    notifications.push((
+                        format!("rule-{}", i),
+                        Notification {
+                            summary: format!(
+                                "{}: {} {}",
+                                location,
+                                name,
+                                threshold(*above, *below, unit)
+                            ),
+                            body: format!(
+                                "{} {}{} on {}\n{}",
+                                name,
+                                field.value(&day.day),
+                                unit,
+                                day.date.format("%a %d %b"),
+                                summarize_day(day, language)
+                            ),
+                            urgent: false,
+                        },
+                    ));
+                }
+            }
+            Trigger::Alert => {
+                for alert in &weather.alerts.alert {
+                    notifications.push((
+                        // each alert notifies once per rule
+                        format!(
+                            "rule-{}-alert-{}-{}",
+                            i,
+                            alert.event,
+                            alert
+                                .effective
+                                .map(|time| time.to_rfc3339())
+                                .unwrap_or_default()
+                        ),
+                        Notification {
+                            summary: format!("{}: {}", location, alert.event),
+                            body: with_summary(
+                                weather,
+                                now.date(),
+                                alert.headline.clone(),
+                                language,
+                            ),
+                            urgent: matches!(alert.severity, Severity::Severe | Severity::Extreme),
+                        },
+                    ));
+                }
+            }
+        }
+
+        for (key, mut notification) in notifications {
+            if let Some(template) = &rule.template {
+                let template = template
+                    .replace("{summary}", &notification.summary)
+                    .replace("{body}", &notification.body);
+                notification.body = render_template(&template, weather);
+            }
+
+            firings.push(Firing {
+                key,
+                rule: i,
+                notification,
+            });
+        }
+    }
+
+    firings
+}
+
+impl Watcher {
+    /// Firings for the rules that hold now but didn't at the last check of the location
+    pub fn update(&mut self, location: &str, firings: Vec<Firing>) -> Vec<Firing> {
+        let previous = self.active.remove(location).unwrap_or_default();
+        let mut active = HashSet::new();
+        let mut notifications = vec![];
+
+        for firing in firings {
+            if active.insert(firing.key.clone()) && !previous.contains(&firing.key) {
+                notifications.push(firing);
+            }
+        }
+
+        self.active.insert(location.to_string(), active);
+        notifications
+    }
+}
+
+/// Checks each location every `interval_minutes`, sending notifications for rules that start to
+/// hold. With `once` the locations are only checked once
+pub fn run(
+    config: &WatchConfig,
+    locations: &[String],
+    day_parts: &[DayPart],
+    sinks: &HashMap<String, Box<dyn Notifier>>,
+    language: Language,
+    recorder: Option<&Recorder>,
+    once: bool,
+) {
+    let mut watcher = Watcher::default();
+    let query = Query::Forecast(config.forecast_days());
+
+    loop {
+        for location in locations {
+            let weather = match get_weather(location, &query) {
+                Ok(data) => data,
+                Err(e) => {
+                    // keep what was active, so a failed check doesn't cause repeat notifications
+                    eprintln!("Unable to fetch weather for {}: {}", location, e);
+                    continue;
+                }
+            };
+            record(recorder, location, &weather);
+
+            let firings = evaluate(&weather, &config.rules, day_parts, language);
+            for firing in watcher.update(location, firings) {
+                let notification = &firing.notification;
+                println!("{}: {}", notification.summary, notification.body);
+
+                for name in config.rules[firing.rule].sink_names() {
+                    let Some(sink) = sinks.get(name) else {
+                        continue;
+                    };
+                    if let Err(e) = sink.notify(notification) {
+                        eprintln!("Unable to send notification to {}: {}", name, e);
+                    }
+                }
+            }
+        }
+
+        if once {
+            break;
+        }
+        thread::sleep(Duration::from_secs(config.interval_minutes * 60));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::fixture_weather;
+
+    fn rules(toml: &str) -> Vec<Rule> {
+        #[derive(Deserialize)]
+        struct Rules {
+            rules: Vec<Rule>,
+        }
+
+        toml::from_str::<Rules>(toml).unwrap().rules
+    }
+
+    #[test]
+    fn evaluates_hourly_daily_and_alert_rules() {
+        let weather = fixture_weather();
+        let rules = rules(
+            r#"
+            [[rules]]
+            kind = "hourly"
+            field = "temp_c"
+            below = 100
+
+            [[rules]]
+            kind = "hourly"
+            field = "temp_c"
+            above = 100
+
+            [[rules]]
+            kind = "daily"
+            field = "daily_chance_of_rain"
+            above = 60
+            days = 2
+
+            [[rules]]
+            kind = "alert"
+            "#,
+        );
+
+        let firings = evaluate(&weather, &rules, &DayPart::defaults(), Language::En);
+        let keys: Vec<&str> = firings.iter().map(|firing| firing.key.as_str()).collect();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0], "rule-0");
+        assert_eq!(keys[1], "rule-2");
+        assert!(keys[2].starts_with("rule-3-alert-"));
+
+        // each body ends with the summary of the day it is about
+        let summaries: Vec<String> = weather
+            .forecast
+            .forecastday
+            .iter()
+            .map(|day| summarize_day(day, Language::En))
+            .collect();
+        assert_eq!(
+            firings[0].notification.body,
+            format!("Temperature 9C at Wed 09:00\n{}", summaries[0])
+        );
+        assert!(firings[1].notification.body.starts_with("Chance of rain"));
+        assert!(firings[1]
+            .notification
+            .body
+            .ends_with(&format!("on Thu 02 May\n{}", summaries[1])));
+        assert!(firings[2].notification.body.ends_with(&summaries[0]));
+    }
+
+    #[test]
+    fn evaluates_rules_for_a_day_part() {
+        let weather = fixture_weather();
+        let rules = rules(
+            r#"
+            [[rules]]
+            kind = "hourly"
+            field = "temp_c"
+            below = 100
+            day_part = "night"
+            "#,
+        );
+
+        let firings = evaluate(&weather, &rules, &DayPart::defaults(), Language::En);
+        assert_eq!(firings.len(), 1);
+        assert!(firings[0].notification.body.contains("at Wed 22:00\n"));
+        assert!(rules[0].trigger.validate(&[]).is_err());
+    }
+
+    #[test]
+    fn fills_in_rule_templates() {
+        let weather = fixture_weather();
+        let rules = rules(
+            r#"
+            [[rules]]
+            kind = "alert"
+            sinks = ["team"]
+            template = "{summary} ({alerts} active), now {temp_c}C"
+            "#,
+        );
+
+        let firings = evaluate(&weather, &rules, &[], Language::En);
+        assert_eq!(
+            firings[0].notification.body,
+            format!(
+                "{} (1 active), now {}C",
+                firings[0].notification.summary,
+                weather.current.unwrap().temp_c
+            )
+        );
+
+        let config = WatchConfig {
+            rules,
+            ..WatchConfig::default()
+        };
+        assert!(config.validate(&[]).is_err());
+
+        let config = WatchConfig {
+            rules: vec![],
+            ..WatchConfig::default()
+        };
+        assert!(config.validate(&[]).is_ok());
+        let config = WatchConfig {
+            interval_minutes: 0,
+            ..config
+        };
+        assert!(config.validate(&[]).is_err());
+    }
+
+    #[test]
+    fn fetches_enough_days_for_daily_rules() {
+        let daily = |days: usize| {
+            rules(&format!(
+                "[[rules]]\nkind = \"daily\"\nfield = \"maxtemp_c\"\nabove = 25\ndays = {}",
+                days
+            ))
+        };
+        let config = |rules: Vec<Rule>| WatchConfig {
+            rules,
+            ..WatchConfig::default()
+        };
+
+        assert_eq!(WatchConfig::default().forecast_days(), WATCH_FORECAST_DAYS);
+        assert_eq!(config(daily(1)).forecast_days(), WATCH_FORECAST_DAYS);
+        assert_eq!(config(daily(5)).forecast_days(), 5);
+        assert!(config(daily(5)).validate(&[]).is_ok());
+        assert!(config(daily(0)).validate(&[]).is_err());
+        assert!(config(daily(15)).validate(&[]).is_err());
+    }
+
+    #[test]
+    fn notifies_once_until_rule_stops_holding() {
+        let weather = fixture_weather();
+        let rules = rules("[[rules]]\nkind = \"alert\"");
+        let mut watcher = Watcher::default();
+
+        let firings = evaluate(&weather, &rules, &[], Language::En);
+        assert_eq!(watcher.update("London", firings.clone()).len(), 1);
+        assert!(watcher.update("London", firings.clone()).is_empty());
+        // other locations are tracked separately
+        assert_eq!(watcher.update("Paris", firings.clone()).len(), 1);
+
+        assert!(watcher.update("London", vec![]).is_empty());
+        assert_eq!(watcher.update("London", firings).len(), 1);
+    }
+}
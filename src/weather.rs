@@ -1,11 +1,30 @@
-use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use chrono::{
+    DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone,
+    Timelike,
+};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::{
+    astronomy::{Moon, MoonPhase, SolarDay},
+    derived::{Derived, PressureTendency, PRESSURE_TENDENCY_HOURS},
+};
+
+/// Largest difference between provider and locally computed sun times before the provider is ignored
+const MAX_SUN_TIME_DEVIATION_MINUTES: i64 = 30;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Weather {
     pub location: Location,
-    pub current: Current,
+    /// Not included in historical, long-range or marine data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<Current>,
     pub forecast: Forecast,
+    #[serde(default)]
+    pub alerts: Alerts,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -16,11 +35,13 @@ pub struct Location {
     pub country: String,
     pub lat: f64,
     pub lon: f64,
+    /// IANA time zone, e.g. Europe/London, which gives the UTC offset of any local time
     #[serde(rename = "tz_id")]
-    pub tz_id: String,
+    pub tz_id: Tz,
     #[serde(rename = "localtime_epoch")]
     pub localtime_epoch: i64,
-    pub localtime: String,
+    #[serde(with = "local_datetime")]
+    pub localtime: NaiveDateTime,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -28,8 +49,8 @@ pub struct Location {
 pub struct Current {
     #[serde(rename = "last_updated_epoch")]
     pub last_updated_epoch: i64,
-    #[serde(rename = "last_updated")]
-    pub last_updated: String,
+    #[serde(rename = "last_updated", with = "local_datetime")]
+    pub last_updated: NaiveDateTime,
     #[serde(rename = "temp_c")]
     pub temp_c: f64,
     #[serde(rename = "temp_f")]
@@ -80,6 +101,49 @@ pub struct Current {
     pub gust_mph: f64,
     #[serde(rename = "gust_kph")]
     pub gust_kph: f64,
+    #[serde(
+        rename = "air_quality",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub air_quality: Option<AirQuality>,
+}
+
+/// Pollutant concentrations in μg/m3, with the US EPA and UK DEFRA air quality indices
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AirQuality {
+    pub co: f64,
+    pub no2: f64,
+    pub o3: f64,
+    pub so2: f64,
+    #[serde(rename = "pm2_5")]
+    pub pm2_5: f64,
+    pub pm10: f64,
+    #[serde(rename = "us-epa-index")]
+    pub us_epa_index: i64,
+    #[serde(rename = "gb-defra-index")]
+    pub gb_defra_index: i64,
+}
+
+/// Health category of the US EPA air quality index (1-6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpaCategory {
+    Good,
+    Moderate,
+    UnhealthyForSensitiveGroups,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+/// Band of the UK DEFRA daily air quality index (1-10)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefraBand {
+    Low,
+    Moderate,
+    High,
+    VeryHigh,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -90,6 +154,25 @@ pub struct Condition {
     pub code: i64,
 }
 
+/// Broad category of a weather condition, shared by every provider code that describes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionKind {
+    Clear,
+    PartlyCloudy,
+    Cloudy,
+    Overcast,
+    Fog,
+    Drizzle,
+    Rain,
+    HeavyRain,
+    Sleet,
+    Snow,
+    HeavySnow,
+    Hail,
+    Thunder,
+    Unknown,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Forecast {
@@ -99,14 +182,17 @@ pub struct Forecast {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Forecastday {
-    pub date: String,
+    pub date: NaiveDate,
     #[serde(rename = "date_epoch")]
     pub date_epoch: i64,
     pub day: Day,
+    /// Filled in locally by `Weather::fill_astro` when missing
+    #[serde(default)]
     pub astro: Astro,
     pub hour: Vec<Hour>,
 }
 
+/// Marine responses omit the snow and rain chance fields, which are left at their defaults
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Day {
@@ -130,40 +216,46 @@ pub struct Day {
     pub totalprecip_mm: f64,
     #[serde(rename = "totalprecip_in")]
     pub totalprecip_in: f64,
-    #[serde(rename = "totalsnow_cm")]
+    #[serde(rename = "totalsnow_cm", default)]
     pub totalsnow_cm: f64,
     #[serde(rename = "avgvis_km")]
     pub avgvis_km: f64,
     #[serde(rename = "avgvis_miles")]
     pub avgvis_miles: f64,
     pub avghumidity: i64,
-    #[serde(rename = "daily_will_it_rain")]
+    #[serde(rename = "daily_will_it_rain", default)]
     pub daily_will_it_rain: i64,
-    #[serde(rename = "daily_chance_of_rain")]
+    #[serde(rename = "daily_chance_of_rain", default)]
     pub daily_chance_of_rain: i64,
-    #[serde(rename = "daily_will_it_snow")]
+    #[serde(rename = "daily_will_it_snow", default)]
     pub daily_will_it_snow: i64,
-    #[serde(rename = "daily_chance_of_snow")]
+    #[serde(rename = "daily_chance_of_snow", default)]
     pub daily_chance_of_snow: i64,
-    pub condition: Condition2,
+    pub condition: Condition,
     pub uv: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Condition2 {
-    pub text: String,
-    pub icon: String,
-    pub code: i64,
+    #[serde(
+        rename = "air_quality",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub air_quality: Option<AirQuality>,
+    /// Only included in marine data
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tides: Vec<Tides>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Astro {
-    pub sunrise: String,
-    pub sunset: String,
-    pub moonrise: String,
-    pub moonset: String,
+    /// None when the event doesn't happen on this day, e.g. during polar night
+    #[serde(default, with = "astro_time")]
+    pub sunrise: Option<NaiveTime>,
+    #[serde(default, with = "astro_time")]
+    pub sunset: Option<NaiveTime>,
+    #[serde(default, with = "astro_time")]
+    pub moonrise: Option<NaiveTime>,
+    #[serde(default, with = "astro_time")]
+    pub moonset: Option<NaiveTime>,
     #[serde(rename = "moon_phase")]
     pub moon_phase: String,
     #[serde(rename = "moon_illumination")]
@@ -174,19 +266,21 @@ pub struct Astro {
     pub is_sun_up: i64,
 }
 
+/// Marine responses omit the snow and rain chance fields, which are left at their defaults
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Hour {
     #[serde(rename = "time_epoch")]
     pub time_epoch: i64,
-    pub time: String,
+    #[serde(with = "local_datetime")]
+    pub time: NaiveDateTime,
     #[serde(rename = "temp_c")]
     pub temp_c: f64,
     #[serde(rename = "temp_f")]
     pub temp_f: f64,
     #[serde(rename = "is_day")]
     pub is_day: i64,
-    pub condition: Condition3,
+    pub condition: Condition,
     #[serde(rename = "wind_mph")]
     pub wind_mph: f64,
     #[serde(rename = "wind_kph")]
@@ -203,7 +297,7 @@ pub struct Hour {
     pub precip_mm: f64,
     #[serde(rename = "precip_in")]
     pub precip_in: f64,
-    #[serde(rename = "snow_cm")]
+    #[serde(rename = "snow_cm", default)]
     pub snow_cm: f64,
     pub humidity: i64,
     pub cloud: i64,
@@ -223,13 +317,13 @@ pub struct Hour {
     pub dewpoint_c: f64,
     #[serde(rename = "dewpoint_f")]
     pub dewpoint_f: f64,
-    #[serde(rename = "will_it_rain")]
+    #[serde(rename = "will_it_rain", default)]
     pub will_it_rain: i64,
-    #[serde(rename = "chance_of_rain")]
+    #[serde(rename = "chance_of_rain", default)]
     pub chance_of_rain: i64,
-    #[serde(rename = "will_it_snow")]
+    #[serde(rename = "will_it_snow", default)]
     pub will_it_snow: i64,
-    #[serde(rename = "chance_of_snow")]
+    #[serde(rename = "chance_of_snow", default)]
     pub chance_of_snow: i64,
     #[serde(rename = "vis_km")]
     pub vis_km: f64,
@@ -240,93 +334,916 @@ pub struct Hour {
     #[serde(rename = "gust_kph")]
     pub gust_kph: f64,
     pub uv: f64,
+    /// Significant wave height in metres, marine data only
+    #[serde(rename = "sig_ht_mt", skip_serializing_if = "Option::is_none")]
+    pub sig_ht_mt: Option<f64>,
+    #[serde(rename = "swell_ht_mt", skip_serializing_if = "Option::is_none")]
+    pub swell_ht_mt: Option<f64>,
+    #[serde(rename = "swell_ht_ft", skip_serializing_if = "Option::is_none")]
+    pub swell_ht_ft: Option<f64>,
+    #[serde(rename = "swell_dir", skip_serializing_if = "Option::is_none")]
+    pub swell_dir: Option<f64>,
+    #[serde(rename = "swell_dir_16_point", skip_serializing_if = "Option::is_none")]
+    pub swell_dir_16_point: Option<String>,
+    #[serde(rename = "swell_period_secs", skip_serializing_if = "Option::is_none")]
+    pub swell_period_secs: Option<f64>,
+    #[serde(rename = "water_temp_c", skip_serializing_if = "Option::is_none")]
+    pub water_temp_c: Option<f64>,
+    #[serde(rename = "water_temp_f", skip_serializing_if = "Option::is_none")]
+    pub water_temp_f: Option<f64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Condition3 {
-    pub text: String,
-    pub icon: String,
-    pub code: i64,
+pub struct Tides {
+    pub tide: Vec<Tide>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tide {
+    #[serde(rename = "tide_time", with = "local_datetime")]
+    pub tide_time: NaiveDateTime,
+    /// Height relative to mean sea level, the API sends this as a string
+    #[serde(rename = "tide_height_mt", deserialize_with = "number_from_string")]
+    pub tide_height_mt: f64,
+    /// HIGH or LOW
+    #[serde(rename = "tide_type")]
+    pub tide_type: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alerts {
+    pub alert: Vec<Alert>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Alert {
+    pub headline: String,
+    pub msgtype: String,
+    pub severity: Severity,
+    pub urgency: Urgency,
+    pub areas: String,
+    pub category: String,
+    pub certainty: String,
+    pub event: String,
+    pub note: String,
+    #[serde(with = "alert_datetime")]
+    pub effective: Option<DateTime<FixedOffset>>,
+    #[serde(with = "alert_datetime")]
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub desc: String,
+    pub instruction: String,
+}
+
+/// CAP alert severity
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Extreme,
+    Severe,
+    Moderate,
+    Minor,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+/// CAP alert urgency
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Urgency {
+    Immediate,
+    Expected,
+    Future,
+    Past,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl fmt::Display for Weather {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.current {
+            Some(current) => write!(
+                f,
+                "{}\n{}\n{}C / {}F",
+                &self.location.name, &current.condition.text, &current.temp_c, &current.temp_f
+            ),
+            None => write!(f, "{}", &self.location.name),
+        }
+    }
 }
 
 impl Weather {
-    pub fn to_string(&self) -> String {
-        format!(
-            "{:#?}\n{:#?}\n{:#?}C\n{:#?}F",
-            &self.location.name,
-            &self.current.condition.text,
-            &self.current.temp_c,
-            &self.current.temp_f
+    /// Fills in astro data missing from the provider's response using the location's coordinates
+    /// Sunrise/sunset times that disagree with the local calculation are replaced as well
+    /// Each date uses its own UTC offset, so days on either side of a DST change are both right
+    pub fn fill_astro(&mut self) {
+        for day in &mut self.forecast.forecastday {
+            let date = day.date;
+            let Some(utc_offset) = self.location.utc_offset_on(date) else {
+                continue;
+            };
+
+            day.astro.fill(
+                &SolarDay::new(self.location.lat, self.location.lon, date, utc_offset),
+                &Moon::new(date, utc_offset),
+            );
+        }
+    }
+
+    /// Forecast for the location's current local date
+    pub fn current_day(&self) -> Option<&Forecastday> {
+        let today = self.location.localtime.date();
+        self.forecast
+            .forecastday
+            .iter()
+            .find(|day| day.date == today)
+    }
+
+    /// Every hourly record, in order, across all fetched days
+    pub fn hours(&self) -> impl Iterator<Item = &Hour> {
+        self.forecast.forecastday.iter().flat_map(|day| &day.hour)
+    }
+
+    /// Hourly record covering the given local time, e.g. 14:35 is covered by the 14:00 record
+    pub fn hour_at(&self, datetime: NaiveDateTime) -> Option<&Hour> {
+        let start = start_of_hour(datetime);
+        self.hours().find(|hour| hour.time == start)
+    }
+
+    /// Hourly record closest to the given local time, even if it is outside the fetched days
+    pub fn nearest_hour(&self, now: NaiveDateTime) -> Option<&Hour> {
+        self.hour_at(now + Duration::minutes(30)).or_else(|| {
+            self.hours()
+                .min_by_key(|hour| (hour.time - now).num_seconds().abs())
+        })
+    }
+
+    /// Hourly records from `start` (inclusive) up to `end` (exclusive), spanning days as needed
+    pub fn hours_between(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = &Hour> {
+        let start = start_of_hour(start);
+        self.hours()
+            .filter(move |hour| hour.time >= start && hour.time < end)
+    }
+
+    /// Hourly records for the current hour at the location and the `n - 1` hours after it
+    pub fn next_n_hours(&self, n: usize) -> impl Iterator<Item = &Hour> {
+        let now = self.location.localtime;
+        self.hours_between(now, start_of_hour(now) + Duration::hours(n as i64))
+    }
+
+    /// Pressure change (hPa) over the three hours up to the location's local time and its tendency
+    /// Returns None if the earlier hour wasn't fetched
+    pub fn pressure_tendency(&self) -> Option<(f64, PressureTendency)> {
+        let now = self.location.localtime;
+        let latest = self.hour_at(now)?;
+        let earlier = self.hour_at(now - Duration::hours(PRESSURE_TENDENCY_HOURS))?;
+        let change = latest.pressure_mb - earlier.pressure_mb;
+
+        Some((change, PressureTendency::from_change(change)))
+    }
+}
+
+/// Truncates a time to the start of its hour
+fn start_of_hour(datetime: NaiveDateTime) -> NaiveDateTime {
+    datetime
+        .date()
+        .and_hms_opt(datetime.hour(), 0, 0)
+        .unwrap_or(datetime)
+}
+
+impl Current {
+    /// Metrics derived from the current temperature, humidity, wind and dew point
+    pub fn derived(&self) -> Derived {
+        Derived::new(
+            self.temp_c,
+            self.humidity as f64,
+            self.wind_kph,
+            self.dewpoint_c,
+        )
+    }
+}
+
+impl Hour {
+    /// Metrics derived from the hour's temperature, humidity, wind and dew point
+    pub fn derived(&self) -> Derived {
+        Derived::new(
+            self.temp_c,
+            self.humidity as f64,
+            self.wind_kph,
+            self.dewpoint_c,
         )
     }
+}
 
-    pub fn get_morning_data(&self) -> Option<&Hour> {
-        self.get_data_for_time(&self.forecast.forecastday[0], 6)
+impl Forecastday {
+    /// High and low tides for the day, empty unless marine data was requested
+    pub fn tides(&self) -> impl Iterator<Item = &Tide> {
+        self.day.tides.iter().flat_map(|tides| &tides.tide)
     }
+}
 
-    pub fn get_afternoon_data(&self) -> Option<&Hour> {
-        self.get_data_for_time(&self.forecast.forecastday[0], 12)
+impl Condition {
+    pub fn kind(&self) -> ConditionKind {
+        ConditionKind::from_code(self.code)
     }
+}
 
-    pub fn get_evening_data(&self) -> Option<&Hour> {
-        self.get_data_for_time(&self.forecast.forecastday[0], 18)
+impl ConditionKind {
+    /// Maps weatherapi.com condition codes, see https://www.weatherapi.com/docs/weather_conditions.json
+    pub fn from_code(code: i64) -> ConditionKind {
+        match code {
+            1000 => ConditionKind::Clear,
+            1003 => ConditionKind::PartlyCloudy,
+            1006 => ConditionKind::Cloudy,
+            1009 => ConditionKind::Overcast,
+            1030 | 1135 | 1147 => ConditionKind::Fog,
+            1072 | 1150 | 1153 | 1168 | 1171 => ConditionKind::Drizzle,
+            1063 | 1180 | 1183 | 1186 | 1189 | 1198 | 1240 => ConditionKind::Rain,
+            1192 | 1195 | 1201 | 1243 | 1246 => ConditionKind::HeavyRain,
+            1069 | 1204 | 1207 | 1249 | 1252 => ConditionKind::Sleet,
+            1066 | 1114 | 1210 | 1213 | 1216 | 1219 | 1255 => ConditionKind::Snow,
+            1117 | 1222 | 1225 | 1258 => ConditionKind::HeavySnow,
+            1237 | 1261 | 1264 => ConditionKind::Hail,
+            1087 | 1273 | 1276 | 1279 | 1282 => ConditionKind::Thunder,
+            _ => ConditionKind::Unknown,
+        }
     }
 
-    pub fn get_night_data(&self) -> Option<&Hour> {
-        self.get_data_for_time(&self.forecast.forecastday[0], 0)
+    pub fn icon(&self) -> &'static str {
+        match self {
+            ConditionKind::Clear => "☀",
+            ConditionKind::PartlyCloudy => "⛅",
+            ConditionKind::Cloudy | ConditionKind::Overcast => "☁",
+            ConditionKind::Fog => "🌫",
+            ConditionKind::Drizzle | ConditionKind::Rain => "🌧",
+            ConditionKind::HeavyRain => "☔",
+            ConditionKind::Sleet | ConditionKind::Hail => "🌨",
+            ConditionKind::Snow | ConditionKind::HeavySnow => "❄",
+            ConditionKind::Thunder => "⛈",
+            ConditionKind::Unknown => "?",
+        }
     }
 
-    /// Returns list of weather forecast data for corresponding number of days
-    /// Data includes the hour of morning/afternoon/evening/night for each subsequent day
-    pub fn get_data_for_days(&self, days: usize) -> Option<Vec<&Forecastday>> {
-        let mut result: Vec<&Forecastday> = vec![];
-        if days > 0 && days <= 7 {
-            for i in 0..days {
-                let curr = &self.forecast.forecastday[i];
-                result.push(curr);
-            }
-            Some((result))
-        } else {
-            None
+    /// Short machine-friendly name, e.g. for styling status bars from template output
+    pub fn class(&self) -> &'static str {
+        match self {
+            ConditionKind::Clear => "clear",
+            ConditionKind::PartlyCloudy => "partly-cloudy",
+            ConditionKind::Cloudy => "cloudy",
+            ConditionKind::Overcast => "overcast",
+            ConditionKind::Fog => "fog",
+            ConditionKind::Drizzle => "drizzle",
+            ConditionKind::Rain => "rain",
+            ConditionKind::HeavyRain => "heavy-rain",
+            ConditionKind::Sleet => "sleet",
+            ConditionKind::Snow => "snow",
+            ConditionKind::HeavySnow => "heavy-snow",
+            ConditionKind::Hail => "hail",
+            ConditionKind::Thunder => "thunder",
+            ConditionKind::Unknown => "unknown",
         }
     }
 
-    fn get_data_for_time<'a>(&'a self, day: &'a Forecastday, time: u32) -> Option<&Hour> {
-        for hour in &day.hour {
-            let split_hour: Vec<&str> = hour.time.split_whitespace().collect();
-            let hour_str = split_hour[1];
+    /// Whether anything is falling from the sky
+    pub fn is_precipitation(&self) -> bool {
+        !matches!(
+            self,
+            ConditionKind::Clear
+                | ConditionKind::PartlyCloudy
+                | ConditionKind::Cloudy
+                | ConditionKind::Overcast
+                | ConditionKind::Fog
+                | ConditionKind::Unknown
+        )
+    }
+}
+
+impl AirQuality {
+    pub fn epa_category(&self) -> Option<EpaCategory> {
+        match self.us_epa_index {
+            1 => Some(EpaCategory::Good),
+            2 => Some(EpaCategory::Moderate),
+            3 => Some(EpaCategory::UnhealthyForSensitiveGroups),
+            4 => Some(EpaCategory::Unhealthy),
+            5 => Some(EpaCategory::VeryUnhealthy),
+            6 => Some(EpaCategory::Hazardous),
+            _ => None,
+        }
+    }
 
-            if hour_str == self.time_str(time) {
-                return Some(&hour);
-            }
+    pub fn defra_band(&self) -> Option<DefraBand> {
+        match self.gb_defra_index {
+            1..=3 => Some(DefraBand::Low),
+            4..=6 => Some(DefraBand::Moderate),
+            7..=9 => Some(DefraBand::High),
+            10 => Some(DefraBand::VeryHigh),
+            _ => None,
         }
-        None
     }
+}
 
-    /// Formats hour in the form 0x:00
-    fn time_str(&self, hour: u32) -> String {
-        let mut hour_str = format!("{}:00", hour);
+impl fmt::Display for EpaCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EpaCategory::Good => "Good",
+            EpaCategory::Moderate => "Moderate",
+            EpaCategory::UnhealthyForSensitiveGroups => "Unhealthy for sensitive groups",
+            EpaCategory::Unhealthy => "Unhealthy",
+            EpaCategory::VeryUnhealthy => "Very unhealthy",
+            EpaCategory::Hazardous => "Hazardous",
+        };
 
-        if hour < 10 {
-            hour_str = format!("0{}:00", hour);
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for DefraBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DefraBand::Low => "Low",
+            DefraBand::Moderate => "Moderate",
+            DefraBand::High => "High",
+            DefraBand::VeryHigh => "Very high",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl Alerts {
+    pub fn is_empty(&self) -> bool {
+        self.alert.is_empty()
+    }
+
+    /// Severity of the most severe active alert, if any
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.alert
+            .iter()
+            .map(|alert| alert.severity)
+            .max_by_key(|severity| severity.rank())
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:?}] {}", self.severity, self.event)?;
+
+        if let Some(expires) = self.expires {
+            write!(f, " until {}", expires.format("%Y-%m-%d %H:%M"))?;
         }
 
-        hour_str
+        Ok(())
     }
 }
 
-/// Returns list of weather forecast data for corresponding number of days
-/// Data includes the hour of morning/afternoon/evening/night for each subsequent day
-pub fn get_data_for_days(weather: &Weather, days: usize) -> Option<Vec<&Forecastday>> {
-    let mut result: Vec<&Forecastday> = vec![];
-    if days > 0 && days <= 7 {
-        for i in 0..days {
-            let curr = &weather.forecast.forecastday[i];
-            result.push(curr);
+impl Severity {
+    /// Position of the severity from least (0) to most severe
+    fn rank(&self) -> u8 {
+        match self {
+            Severity::Unknown => 0,
+            Severity::Minor => 1,
+            Severity::Moderate => 2,
+            Severity::Severe => 3,
+            Severity::Extreme => 4,
+        }
+    }
+
+    /// Exit code used by one-shot output when an alert of this severity is active
+    /// Codes 1 and 2 are left for fetch and usage errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Severity::Unknown | Severity::Minor => 3,
+            Severity::Moderate => 4,
+            Severity::Severe => 5,
+            Severity::Extreme => 6,
         }
-        Some((result))
-    } else {
-        None
+    }
+}
+
+impl Location {
+    /// Local time at the location with its time zone. Times repeated when the clocks go back
+    /// resolve to the earlier one, times skipped when they go forward are None
+    pub fn localize(&self, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+        self.tz_id.from_local_datetime(&local).earliest()
+    }
+
+    /// Offset of local time from UTC (in seconds) on the given date, taken at noon since
+    /// clocks change at night
+    pub fn utc_offset_on(&self, date: NaiveDate) -> Option<i32> {
+        let noon = self.localize(date.and_hms_opt(12, 0, 0)?)?;
+
+        Some(noon.offset().fix().local_minus_utc())
+    }
+
+    /// Sun position calculator for the location on the given date
+    pub fn solar_day(&self, date: NaiveDate) -> Option<SolarDay> {
+        Some(SolarDay::new(
+            self.lat,
+            self.lon,
+            date,
+            self.utc_offset_on(date)?,
+        ))
+    }
+}
+
+impl Astro {
+    /// Length of time between sunrise and sunset
+    pub fn daylight(&self) -> Option<Duration> {
+        Some(self.sunset? - self.sunrise?)
+    }
+
+    /// Unicode glyph matching the moon phase name reported by the API
+    pub fn moon_phase_glyph(&self) -> &str {
+        MoonPhase::from_name(&self.moon_phase).map_or("?", |phase| phase.glyph())
+    }
+
+    fn fill(&mut self, solar_day: &SolarDay, moon: &Moon) {
+        fill_sun_time(&mut self.sunrise, solar_day.sunrise());
+        fill_sun_time(&mut self.sunset, solar_day.sunset());
+
+        if MoonPhase::from_name(&self.moon_phase).is_none() {
+            self.moon_phase = moon.phase.name().to_string();
+            self.moon_illumination = moon.illumination.round() as i64;
+        }
+    }
+}
+
+/// Replaces a provided sun time if it is missing or too far from the computed time
+/// Left untouched when the sun doesn't rise/set by the local calculation, leaving the
+/// provider's (more precise) account of borderline polar days
+fn fill_sun_time(provided: &mut Option<NaiveTime>, computed: Option<NaiveTime>) {
+    let Some(computed) = computed else {
+        return;
+    };
+
+    let is_valid = provided.is_some_and(|time| {
+        (time - computed).num_minutes().abs() <= MAX_SUN_TIME_DEVIATION_MINUTES
+    });
+
+    if !is_valid {
+        *provided = Some(computed);
+    }
+}
+
+/// Accepts numbers that are sent either as JSON numbers or as strings, e.g. "1.23"
+fn number_from_string<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(f64),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(number) => Ok(number),
+        NumberOrString::String(string) => string.trim().parse().map_err(de::Error::custom),
+    }
+}
+
+/// Local date and time in the form 2024-05-01 09:00, the hour of `Location::localtime` isn't padded
+mod local_datetime {
+    use chrono::NaiveDateTime;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%d %H:%M";
+
+    pub fn serialize<S>(datetime: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&datetime.format(FORMAT))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let datetime = String::deserialize(deserializer)?;
+        NaiveDateTime::parse_from_str(&datetime, FORMAT).map_err(de::Error::custom)
+    }
+}
+
+/// Astro times are given in the form 06:42 AM, or as "No moonrise" etc. when the event doesn't occur
+mod astro_time {
+    use chrono::NaiveTime;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%I:%M %p";
+
+    pub fn serialize<S>(time: &Option<NaiveTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match time {
+            Some(time) => serializer.collect_str(&time.format(FORMAT)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(time) if !time.starts_with("No ") => NaiveTime::parse_from_str(&time, FORMAT)
+                .map(Some)
+                .map_err(de::Error::custom),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Alert times are RFC 3339 timestamps, or empty when the provider doesn't give one
+mod alert_datetime {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(
+        datetime: &Option<DateTime<FixedOffset>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match datetime {
+            Some(datetime) => serializer.serialize_str(&datetime.to_rfc3339()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<FixedOffset>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(datetime) if !datetime.is_empty() => DateTime::parse_from_rfc3339(&datetime)
+                .map(Some)
+                .map_err(de::Error::custom),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Forecast response for London on 2024-05-01, with two days of hours and a moderate alert
+#[cfg(test)]
+const FIXTURE: &str = include_str!("../tests/fixtures/forecast.json");
+
+/// The fixture forecast, shared by the test modules
+#[cfg(test)]
+pub(crate) fn fixture_weather() -> Weather {
+    serde_json::from_str(FIXTURE).expect("fixture should parse")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derived::ThermalStress;
+
+    fn datetime(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn parses_typed_times() {
+        let weather = fixture_weather();
+
+        assert_eq!(weather.location.localtime, datetime("2024-05-01", "09:08"));
+        assert_eq!(
+            weather.forecast.forecastday[0].astro.sunrise,
+            NaiveTime::from_hms_opt(5, 33, 0)
+        );
+        assert_eq!(weather.location.tz_id, chrono_tz::Europe::London);
+    }
+
+    #[test]
+    fn utc_offset_follows_daylight_saving() {
+        let location = fixture_weather().location;
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        // British Summer Time starts at 01:00 UTC on the last Sunday of March
+        assert_eq!(location.utc_offset_on(date(30)), Some(0));
+        assert_eq!(location.utc_offset_on(date(31)), Some(3600));
+        assert!(location.localize(datetime("2024-03-31", "01:30")).is_none());
+        assert_eq!(
+            location
+                .localize(datetime("2024-03-31", "03:00"))
+                .map(|time| time.naive_utc()),
+            Some(datetime("2024-03-31", "02:00"))
+        );
+    }
+
+    #[test]
+    fn fills_astro_with_each_dates_offset() {
+        let mut weather = fixture_weather();
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0);
+        // London either side of the clocks going forward on 2024-03-31
+        let days = [
+            ("2024-03-30", time(5, 41), time(18, 31)),
+            ("2024-03-31", time(6, 38), time(19, 33)),
+        ];
+        for (day, (date, sunrise, sunset)) in weather.forecast.forecastday.iter_mut().zip(days) {
+            day.date = date.parse().unwrap();
+            day.astro.sunrise = sunrise;
+            day.astro.sunset = sunset;
+        }
+        weather.forecast.forecastday[1].astro.sunrise = None;
+
+        weather.fill_astro();
+
+        let first = &weather.forecast.forecastday[0].astro;
+        assert_eq!((first.sunrise, first.sunset), (time(5, 41), time(18, 31)));
+        let second = &weather.forecast.forecastday[1].astro;
+        assert_eq!(second.sunset, time(19, 33));
+        let filled = second.sunrise.unwrap() - time(6, 38).unwrap();
+        assert!(filled.num_minutes().abs() <= 5, "{}", filled);
+    }
+
+    #[test]
+    fn rejects_malformed_times() {
+        let malformed = FIXTURE.replace("\"05:33 AM\"", "\"25:33 AM\"");
+        assert!(serde_json::from_str::<Weather>(&malformed).is_err());
+    }
+
+    #[test]
+    fn rejects_days_and_hours_without_their_times() {
+        let json: serde_json::Value = serde_json::from_str(FIXTURE).unwrap();
+        let without = |pointer: &str, field: &str| {
+            let mut json = json.clone();
+            json.pointer_mut(pointer)
+                .and_then(serde_json::Value::as_object_mut)
+                .unwrap()
+                .remove(field);
+            serde_json::from_value::<Weather>(json)
+        };
+
+        assert!(without("/forecast/forecastday/0", "date").is_err());
+        assert!(without("/forecast/forecastday/0/hour/0", "time").is_err());
+        assert!(without("/forecast/forecastday/0/hour/0", "time_epoch").is_err());
+        assert!(without("/forecast/forecastday/0/day", "maxtemp_c").is_err());
+        // fields marine responses leave out
+        assert!(without("/forecast/forecastday/0/day", "daily_chance_of_rain").is_ok());
+        assert!(without("/forecast/forecastday/0/hour/0", "chance_of_snow").is_ok());
+    }
+
+    #[test]
+    fn hour_at_finds_covering_hour() {
+        let weather = fixture_weather();
+
+        let hour = weather.hour_at(datetime("2024-05-02", "14:35")).unwrap();
+        assert_eq!(hour.time, datetime("2024-05-02", "14:00"));
+        assert!(weather.hour_at(datetime("2024-05-03", "00:00")).is_none());
+    }
+
+    #[test]
+    fn nearest_hour_rounds_and_clamps() {
+        let weather = fixture_weather();
+
+        let rounded = weather
+            .nearest_hour(datetime("2024-05-01", "09:40"))
+            .unwrap();
+        assert_eq!(rounded.time, datetime("2024-05-01", "10:00"));
+
+        // past the end of the forecast the last hour is the closest
+        let clamped = weather
+            .nearest_hour(datetime("2024-05-05", "12:00"))
+            .unwrap();
+        assert_eq!(clamped.time, datetime("2024-05-02", "23:00"));
+    }
+
+    #[test]
+    fn hours_between_spans_days() {
+        let weather = fixture_weather();
+
+        let hours: Vec<NaiveDateTime> = weather
+            .hours_between(
+                datetime("2024-05-01", "22:30"),
+                datetime("2024-05-02", "02:00"),
+            )
+            .map(|hour| hour.time)
+            .collect();
+
+        assert_eq!(
+            hours,
+            vec![
+                datetime("2024-05-01", "22:00"),
+                datetime("2024-05-01", "23:00"),
+                datetime("2024-05-02", "00:00"),
+                datetime("2024-05-02", "01:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_n_hours_starts_at_current_hour() {
+        let weather = fixture_weather();
+
+        let hours: Vec<&Hour> = weather.next_n_hours(3).collect();
+        assert_eq!(hours.len(), 3);
+        assert_eq!(hours[0].time, datetime("2024-05-01", "09:00"));
+
+        // only as many hours as were fetched
+        assert_eq!(weather.next_n_hours(100).count(), 39);
+    }
+
+    #[test]
+    fn derives_metrics_for_each_hour() {
+        let weather = fixture_weather();
+        // 12.5C, 72% humidity, 15 km/h wind and a dew point of 8.5C
+        let hour = weather.hour_at(datetime("2024-05-01", "12:00")).unwrap();
+
+        let derived = hour.derived();
+        assert!((derived.apparent_temp_c - 9.0).abs() < 0.1);
+        assert!(derived.wet_bulb_c > hour.dewpoint_c && derived.wet_bulb_c < hour.temp_c);
+        assert_eq!(derived.cloud_base_m, 500.0);
+        assert_eq!(derived.beaufort.description(), "Gentle breeze");
+        assert_eq!(derived.thermal_stress, ThermalStress::None);
+    }
+
+    #[test]
+    fn current_day_matches_local_date() {
+        let mut weather = fixture_weather();
+        assert_eq!(
+            weather.current_day().map(|day| day.date),
+            NaiveDate::from_ymd_opt(2024, 5, 1)
+        );
+
+        weather.location.localtime = datetime("2024-05-02", "07:00");
+        assert_eq!(
+            weather.current_day().map(|day| day.date),
+            NaiveDate::from_ymd_opt(2024, 5, 2)
+        );
+    }
+
+    #[test]
+    fn empty_forecast_does_not_panic() {
+        let mut weather = fixture_weather();
+        weather.forecast.forecastday.clear();
+        let now = weather.location.localtime;
+
+        assert!(weather.current_day().is_none());
+        assert!(weather.hour_at(now).is_none());
+        assert!(weather.nearest_hour(now).is_none());
+        assert_eq!(weather.next_n_hours(5).count(), 0);
+    }
+
+    #[test]
+    fn parses_alert_severities() {
+        for (text, severity) in [
+            ("Extreme", Severity::Extreme),
+            ("Severe", Severity::Severe),
+            ("Moderate", Severity::Moderate),
+            ("Minor", Severity::Minor),
+            ("", Severity::Unknown),
+            ("Bogus", Severity::Unknown),
+        ] {
+            let alert: Alert =
+                serde_json::from_value(serde_json::json!({ "severity": text })).unwrap();
+            assert_eq!(alert.severity, severity, "{:?}", text);
+        }
+
+        // a missing severity is unknown too
+        let alert: Alert = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(alert.severity, Severity::Unknown);
+    }
+
+    #[test]
+    fn max_severity_picks_the_most_severe_alert() {
+        let alerts = |severities: &[Severity]| Alerts {
+            alert: severities
+                .iter()
+                .map(|&severity| Alert {
+                    severity,
+                    ..Alert::default()
+                })
+                .collect(),
+        };
+
+        assert_eq!(alerts(&[]).max_severity(), None);
+        assert_eq!(
+            alerts(&[Severity::Unknown]).max_severity(),
+            Some(Severity::Unknown)
+        );
+        assert_eq!(
+            alerts(&[Severity::Minor, Severity::Extreme, Severity::Moderate]).max_severity(),
+            Some(Severity::Extreme)
+        );
+        assert_eq!(
+            alerts(&[Severity::Unknown, Severity::Severe, Severity::Minor]).max_severity(),
+            Some(Severity::Severe)
+        );
+        assert_eq!(
+            fixture_weather().alerts.max_severity(),
+            Some(Severity::Moderate)
+        );
+    }
+
+    #[test]
+    fn exit_code_for_each_severity() {
+        for (severity, code) in [
+            (Severity::Unknown, 3),
+            (Severity::Minor, 3),
+            (Severity::Moderate, 4),
+            (Severity::Severe, 5),
+            (Severity::Extreme, 6),
+        ] {
+            assert_eq!(severity.exit_code(), code, "{:?}", severity);
+        }
+    }
+
+    #[test]
+    fn epa_categories_at_each_index() {
+        for (index, category) in [
+            (0, None),
+            (1, Some(EpaCategory::Good)),
+            (2, Some(EpaCategory::Moderate)),
+            (3, Some(EpaCategory::UnhealthyForSensitiveGroups)),
+            (4, Some(EpaCategory::Unhealthy)),
+            (5, Some(EpaCategory::VeryUnhealthy)),
+            (6, Some(EpaCategory::Hazardous)),
+            (7, None),
+            (-1, None),
+        ] {
+            let air_quality = AirQuality {
+                us_epa_index: index,
+                ..AirQuality::default()
+            };
+            assert_eq!(air_quality.epa_category(), category, "{}", index);
+        }
+    }
+
+    #[test]
+    fn defra_bands_at_each_boundary() {
+        for (index, band) in [
+            (0, None),
+            (1, Some(DefraBand::Low)),
+            (3, Some(DefraBand::Low)),
+            (4, Some(DefraBand::Moderate)),
+            (6, Some(DefraBand::Moderate)),
+            (7, Some(DefraBand::High)),
+            (9, Some(DefraBand::High)),
+            (10, Some(DefraBand::VeryHigh)),
+            (11, None),
+            (-1, None),
+        ] {
+            let air_quality = AirQuality {
+                gb_defra_index: index,
+                ..AirQuality::default()
+            };
+            assert_eq!(air_quality.defra_band(), band, "{}", index);
+        }
+    }
+
+    #[test]
+    fn maps_condition_codes_to_kinds() {
+        for (code, kind) in [
+            (1000, ConditionKind::Clear),
+            (1003, ConditionKind::PartlyCloudy),
+            (1006, ConditionKind::Cloudy),
+            (1009, ConditionKind::Overcast),
+            (1135, ConditionKind::Fog),
+            (1153, ConditionKind::Drizzle),
+            (1063, ConditionKind::Rain),
+            (1183, ConditionKind::Rain),
+            (1195, ConditionKind::HeavyRain),
+            (1069, ConditionKind::Sleet),
+            (1213, ConditionKind::Snow),
+            (1225, ConditionKind::HeavySnow),
+            (1237, ConditionKind::Hail),
+            (1087, ConditionKind::Thunder),
+            (1276, ConditionKind::Thunder),
+            (0, ConditionKind::Unknown),
+            (9999, ConditionKind::Unknown),
+        ] {
+            assert_eq!(ConditionKind::from_code(code), kind, "{}", code);
+        }
+
+        assert_eq!(
+            fixture_weather().current.unwrap().condition.kind(),
+            ConditionKind::PartlyCloudy
+        );
+    }
+
+    #[test]
+    fn leaves_out_missing_current_conditions() {
+        let mut weather = fixture_weather();
+        assert!(serde_json::to_value(&weather).unwrap()["current"].is_object());
+
+        weather.current = None;
+        let json = serde_json::to_value(&weather).unwrap();
+        assert!(json.get("current").is_none());
+        assert_eq!(weather.to_string(), "London");
+
+        let parsed: Weather = serde_json::from_value(json).unwrap();
+        assert!(parsed.current.is_none());
     }
 }
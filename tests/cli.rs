@@ -0,0 +1,281 @@
+//! Runs the t-weather binary end to end against its mock weather API
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{self, Child, Command, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use chrono::{Duration, Local};
+use serde_json::Value;
+
+const BIN: &str = env!("CARGO_BIN_EXE_t-weather");
+const KEY: &str = "test";
+
+/// Gives each run its own home directory, so no config, cache or .env is shared
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// `t-weather mock-api` on a free port, stopped when dropped
+struct MockApi {
+    child: Child,
+    url: String,
+}
+
+impl MockApi {
+    fn start() -> MockApi {
+        let mut child = Command::new(BIN)
+            .args(["mock-api", "--address", "127.0.0.1:0"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("t-weather runs");
+
+        // e.g. "Serving a mock weather API at http://127.0.0.1:41234/v1"
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let url = line.split_whitespace().last().unwrap().to_string();
+
+        MockApi { child, url }
+    }
+
+    /// Runs t-weather with these arguments against the mock API, using `key` as the API key
+    fn run(&self, key: Option<&str>, args: &[&str]) -> Output {
+        let home = home();
+        let mut command = Command::new(BIN);
+        command
+            .args(args)
+            .current_dir(&home)
+            .env("HOME", &home)
+            .env("API_URL", &self.url)
+            .env("OPEN_METEO_URL", &self.url)
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("XDG_DATA_HOME");
+        match key {
+            Some(key) => command.env("KEY", key),
+            None => command.env_remove("KEY"),
+        };
+
+        let output = command.output().expect("t-weather runs");
+        let _ = fs::remove_dir_all(&home);
+
+        output
+    }
+}
+
+impl Drop for MockApi {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn home() -> PathBuf {
+    let home = env::temp_dir().join(format!(
+        "t-weather-cli-{}-{}",
+        process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&home).unwrap();
+
+    home
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn once_prints_conditions_and_alerts() {
+    let api = MockApi::start();
+
+    let output = api.run(Some(KEY), &["-l", "London", "-f", "2", "--once"]);
+    let stdout = stdout(&output);
+    assert!(
+        stdout.starts_with("London\nPartly cloudy\n11C"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("2024-05-02: "), "{}", stdout);
+    assert!(stdout.contains("Yellow warning of rain"), "{}", stdout);
+    // the moderate alert sets the exit code
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+}
+
+#[test]
+fn json_and_template_output() {
+    let api = MockApi::start();
+
+    let output = api.run(Some(KEY), &["-l", "London", "-f", "2", "--json"]);
+    let weather: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(weather["location"]["name"], "London");
+    assert_eq!(
+        weather["forecast"]["forecastday"].as_array().unwrap().len(),
+        2
+    );
+    assert_eq!(weather["current"]["temp_c"], 11.0);
+
+    let output = api.run(Some(KEY), &["-l", "London", "-t", "{location}: {temp_c}C"]);
+    assert_eq!(stdout(&output), "London: 11C\n");
+}
+
+#[test]
+fn history_future_and_marine_queries() {
+    let api = MockApi::start();
+
+    let output = api.run(
+        Some(KEY),
+        &[
+            "-l",
+            "London",
+            "--once",
+            "history",
+            "2024-05-01",
+            "--to",
+            "2024-05-02",
+        ],
+    );
+    let printed = stdout(&output);
+    assert!(printed.starts_with("London\n"), "{}", stderr(&output));
+    assert!(
+        printed.contains("2024-05-01: 🌧 Moderate rain"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("2024-05-02: 🌧 Moderate rain"),
+        "{}",
+        printed
+    );
+    // history has no alerts
+    assert_eq!(output.status.code(), Some(0));
+
+    // Open-Meteo's archive needs no key
+    let output = api.run(
+        None,
+        &[
+            "-l",
+            "London",
+            "--once",
+            "history",
+            "2024-05-01",
+            "--provider",
+            "open-meteo",
+        ],
+    );
+    assert!(
+        stdout(&output).contains("2024-05-01: 🌧 Moderate rain, 5.2C / 14.4C"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = api.run(
+        Some(KEY),
+        &["-l", "London", "--json", "history", "2024-05-01"],
+    );
+    let weather: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(weather.get("current").is_none());
+    assert_eq!(weather["forecast"]["forecastday"][0]["date"], "2024-05-01");
+    let output = api.run(
+        Some(KEY),
+        &[
+            "-l",
+            "London",
+            "-t",
+            "{location}: {temp_c}C",
+            "history",
+            "2024-05-01",
+        ],
+    );
+    assert_eq!(stdout(&output), "London: C\n");
+
+    let date = (Local::now().date_naive() + Duration::days(30)).to_string();
+    let output = api.run(Some(KEY), &["-l", "London", "--once", "future", &date]);
+    let printed = stdout(&output);
+    assert!(
+        printed.contains("Long-range forecast"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        printed.contains(&format!("{}: ☀ Sunny", date)),
+        "{}",
+        printed
+    );
+
+    let output = api.run(Some(KEY), &["-l", "London", "--once", "marine"]);
+    assert!(
+        stdout(&output).contains("HIGH 03:02 (3.1m), LOW 09:18 (-2.8m)"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = api.run(Some(KEY), &["-l", "London", "--json", "marine"]);
+    let weather: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let day = &weather["forecast"]["forecastday"][0];
+    assert_eq!(day["day"]["tides"][0]["tide"][0]["tide_type"], "HIGH");
+    assert_eq!(day["hour"][12]["sig_ht_mt"], 0.6);
+    assert_eq!(day["hour"][12]["swell_dir_16_point"], "SW");
+    assert_eq!(day["hour"][12]["water_temp_c"], 11.4);
+
+    let output = api.run(
+        None,
+        &[
+            "-l",
+            "London",
+            "--json",
+            "marine",
+            "--provider",
+            "open-meteo",
+        ],
+    );
+    let weather: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let day = &weather["forecast"]["forecastday"][0];
+    assert!(day["day"].get("tides").is_none());
+    assert_eq!(day["hour"][12]["sig_ht_mt"], 0.6);
+    assert_eq!(day["hour"][12]["water_temp_c"], 11.4);
+}
+
+#[test]
+fn export_writes_rows() {
+    let api = MockApi::start();
+
+    let output = api.run(
+        Some(KEY),
+        &["-l", "London", "export", "--columns", "time,temp_c"],
+    );
+    let stdout = stdout(&output);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("time,temp_c"), "{}", stderr(&output));
+    assert!(lines
+        .next()
+        .is_some_and(|line| line.starts_with("2024-05-01 00:00,")));
+}
+
+#[test]
+fn reports_provider_errors() {
+    let api = MockApi::start();
+
+    for (key, location, message) in [
+        (Some(KEY), "Nowhere", "No matching location found."),
+        (Some("invalid"), "London", "API key provided is invalid"),
+        (
+            Some("exhausted"),
+            "London",
+            "exceeded calls per month quota",
+        ),
+        (None, "London", "No API key found"),
+    ] {
+        let output = api.run(key, &["-l", location, "--once"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr(&output).contains(message), "{}", stderr(&output));
+        assert!(output.stdout.is_empty());
+    }
+}